@@ -1,5 +1,9 @@
 use clap::{Parser, Subcommand};
 use clap_complete::Shell;
+use std::path::PathBuf;
+use std::time::Duration;
+use zerp::picker::PickerKind;
+use zerp::storage::Backend;
 
 /// A simple CLI for managing tasks
 #[derive(Parser)]
@@ -18,6 +22,26 @@ pub struct Cli {
     /// The description of the task
     #[clap(short, long)]
     pub description: Option<String>,
+
+    /// Picker for choosing commands (auto, fzf, skim, dialoguer or menu)
+    #[arg(long, global = true)]
+    pub picker: Option<PickerKind>,
+
+    /// Config file read on top of the system, user and project ones
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Storage path for commands, overriding the configuration
+    #[arg(long, global = true, value_name = "DIR")]
+    pub storage_dir: Option<String>,
+
+    /// Profile from `[profiles.<name>]` whose storage to use
+    #[arg(long, global = true, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Search the storage of every profile at once, as `profile:name`
+    #[arg(long, global = true)]
+    pub all_profiles: bool,
 }
 
 #[derive(Subcommand)]
@@ -26,6 +50,10 @@ pub enum Commands {
     Add {
         /// Name of the command
         name: String,
+
+        /// Template to start from (e.g. bash-strict, python-script, docker-exec)
+        #[arg(short, long)]
+        template: Option<String>,
     },
 
     /// List all available commands
@@ -33,7 +61,57 @@ pub enum Commands {
 
     /// Run a command
     Run {
-        /// Name of the command to run
+        /// Name of the command(s) to run
+        names: Vec<String>,
+
+        /// Run several commands concurrently
+        #[arg(short, long)]
+        parallel: bool,
+
+        /// Maximum number of commands to run at once (with --parallel)
+        #[arg(short, long, requires = "parallel")]
+        jobs: Option<usize>,
+
+        /// Directory to run in, overriding the command's `cwd` metadata
+        #[arg(long)]
+        cwd: Option<String>,
+
+        /// Set an environment variable (KEY=VAL), overriding all other sources
+        #[arg(short, long = "env", value_name = "KEY=VAL", value_parser = zerp::env::parse_key_val)]
+        env: Vec<(String, String)>,
+
+        /// Show the resolved directory and environment without running
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Kill the command if it runs longer than this (e.g. 30s, 5m)
+        #[arg(long, value_parser = humantime::parse_duration)]
+        timeout: Option<Duration>,
+
+        /// Number of times to retry a failed command
+        #[arg(long)]
+        retries: Option<u32>,
+
+        /// Delay before retrying a failed command (default: 1s)
+        #[arg(long, value_parser = humantime::parse_duration)]
+        retry_delay: Option<Duration>,
+
+        /// Double the retry delay after every failed attempt
+        #[arg(long)]
+        backoff: bool,
+
+        /// Don't print an error when the command fails, only exit with its status
+        #[arg(short, long)]
+        quiet_errors: bool,
+
+        /// Run commands that require confirmation without asking
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// Print a command's description, tags and script
+    Show {
+        /// Name of the command to show
         name: Option<String>,
     },
 
@@ -41,12 +119,33 @@ pub enum Commands {
     Edit {
         /// Name of the command to edit
         name: Option<String>,
+
+        /// Edit the command's metadata (e.g. `cwd`) instead of its script
+        #[arg(short, long)]
+        meta: bool,
     },
 
     /// Delete a command
     Delete {
-        /// Name of the command to delete
+        /// Name of the command(s) to delete
+        names: Vec<String>,
+    },
+
+    /// Add or remove a tag on commands
+    Tag {
+        #[clap(subcommand)]
+        action: TagAction,
+    },
+
+    /// Check commands for errors, using shellcheck when installed
+    Lint {
+        /// Name of the command to check
+        #[arg(conflicts_with = "all")]
         name: Option<String>,
+
+        /// Check every command
+        #[arg(short, long)]
+        all: bool,
     },
 
     /// Rename a command
@@ -57,17 +156,54 @@ pub enum Commands {
         new_name: String,
     },
 
+    /// Copy a command and its metadata under a new name
+    Cp {
+        /// Name of the command to copy
+        source: Option<String>,
+        /// Name of the copy
+        destination: Option<String>,
+
+        /// Open the editor on the copy
+        #[arg(short, long)]
+        edit: bool,
+    },
+
+    /// Browse and manage commands in a full-screen terminal UI
+    Ui,
+
+    /// Move every command to another storage backend
+    Migrate {
+        /// Backend to move to (fs, toml, json or sqlite)
+        #[arg(long)]
+        to: Backend,
+        /// Backend to move from (defaults to the configured one)
+        #[arg(long)]
+        from: Option<Backend>,
+        /// Delete the commands from the old backend once they are copied
+        #[arg(long)]
+        remove_source: bool,
+    },
+
     /// Configure the application
     Config {
+        #[clap(subcommand)]
+        action: Option<ConfigAction>,
+
         /// Set the editor to use (e.g., vim, nano, code, hx)
         #[arg(short, long)]
         editor: Option<String>,
 
-        /// Set the storage path for commands (default: ~/.zerp)
+        /// Set the storage path for commands (default: ~/.local/share/zerp)
         #[arg(short, long)]
         storage: Option<String>,
     },
 
+    /// Manage encrypted secrets referenced as {{secret:name}} in commands
+    Secret {
+        #[clap(subcommand)]
+        action: SecretAction,
+    },
+
     /// Generate shell completions
     Completion {
         /// Shell to generate completions for
@@ -75,3 +211,80 @@ pub enum Commands {
         shell: Shell,
     },
 }
+
+#[derive(Subcommand)]
+pub enum ConfigAction {
+    /// Print the effective configuration, merged from every layer
+    Show {
+        /// Print where each value comes from
+        #[arg(long)]
+        origin: bool,
+    },
+    /// Print the effective value of a setting, e.g. `fzf.height`
+    Get {
+        /// Key path of the setting
+        key: String,
+    },
+    /// Set a setting in the user config file
+    Set {
+        /// Key path of the setting, e.g. `picker` or `env.NAME`
+        key: String,
+        /// New value; lists are comma-separated or TOML arrays
+        value: String,
+    },
+    /// Remove a setting from the user config file
+    Unset {
+        /// Key path of the setting
+        key: String,
+    },
+    /// List every setting with its effective value
+    List,
+}
+
+#[derive(Subcommand)]
+pub enum SecretAction {
+    /// Store a secret, prompting for the value when not given
+    Set {
+        /// Name of the secret
+        name: String,
+        /// Value of the secret
+        value: Option<String>,
+    },
+
+    /// Print the value of a secret
+    Get {
+        /// Name of the secret
+        name: String,
+    },
+
+    /// List the names of all secrets
+    List,
+
+    /// Delete a secret
+    Rm {
+        /// Name of the secret
+        name: String,
+    },
+
+    /// Generate a key file to encrypt secrets with instead of a passphrase
+    Keygen,
+}
+
+#[derive(Subcommand)]
+pub enum TagAction {
+    /// Add a tag to commands
+    Add {
+        /// Tag to add
+        tag: String,
+        /// Commands to tag (picked interactively when omitted)
+        names: Vec<String>,
+    },
+
+    /// Remove a tag from commands
+    Rm {
+        /// Tag to remove
+        tag: String,
+        /// Commands to untag (picked interactively when omitted)
+        names: Vec<String>,
+    },
+}
@@ -1,18 +1,94 @@
 use anyhow::{Context, Result};
-use config::{Config, File};
-use dirs::home_dir;
+use colored::Colorize;
+use config::{Config, Environment, File, FileFormat, Source};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Item, TableLike};
+
+use crate::env::GlobalEnv;
+use crate::history::History;
+use crate::paths;
+use crate::picker::{FzfOptions, Picker, PickerKind};
+use crate::runner::RunContext;
+use crate::secret::SecretStore;
+use crate::storage::{Backend, MultiStorage};
+use crate::store::CommandStore;
 
 const CONFIG_FILE_NAME: &str = "config.toml";
 const DEFAULT_EDITOR: &str = "vim";
-const DEFAULT_STORAGE: &str = ".zerp";
+/// Directory holding a project's own config file
+const PROJECT_DIR: &str = ".zerp";
+const SYSTEM_CONFIG_DIR: &str = "/etc/zerp";
+const ENV_PREFIX: &str = "ZERP";
+/// Settings a project config file may change. The others could run
+/// programs or weaken checks, so a repository could abuse them.
+const PROJECT_SETTINGS: [&str; 8] = [
+    "picker",
+    "fzf.height",
+    "fzf.prompt",
+    "fzf.preview_window",
+    "fzf.bind",
+    "lint_on_save",
+    "default_template",
+    "profile",
+];
+/// Prefix of the top-level storage's commands when searching every profile
+/// with another profile selected
+const DEFAULT_PROFILE: &str = "default";
+const DEFAULT_DANGEROUS_PATTERNS: [&str; 4] = ["rm -rf", "DROP TABLE", "kubectl delete", "--force"];
 
+/// Settings missing from every layer keep their default
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct CliConfig {
     pub editor: String,
     pub storage: PathBuf,
+    #[serde(default)]
+    pub storage_backend: Backend,
+    #[serde(default)]
+    pub picker: PickerKind,
+    #[serde(default, skip_serializing_if = "FzfOptions::is_default")]
+    pub fzf: FzfOptions,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_file: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secrets_key: Option<PathBuf>,
+    pub dangerous_patterns: Vec<String>,
+    #[serde(default)]
+    pub lint_on_save: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_template: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Profile whose storage replaces `storage`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// Search the storage of every profile at once, with names prefixed by
+    /// the profile, like `work:deploy`
+    #[serde(default)]
+    pub all_profiles: bool,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+    /// Problems found while loading that didn't stop it, such as unknown
+    /// settings
+    #[serde(skip)]
+    pub warnings: Vec<String>,
+    /// Top-level storage replaced by the selected profile's, still searched
+    /// with `--all-profiles`
+    #[serde(skip)]
+    replaced_storage: Option<Profile>,
+}
+
+/// A named storage, from a `[profiles.<name>]` section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub storage: PathBuf,
+    /// Defaults to the top-level `storage_backend`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage_backend: Option<Backend>,
 }
 
 impl Default for CliConfig {
@@ -23,34 +99,221 @@ impl Default for CliConfig {
         CliConfig {
             editor: default_editor,
             storage: default_storage,
+            storage_backend: Backend::default(),
+            picker: PickerKind::default(),
+            fzf: FzfOptions::default(),
+            env_file: None,
+            secrets_key: None,
+            dangerous_patterns: DEFAULT_DANGEROUS_PATTERNS
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect(),
+            lint_on_save: false,
+            default_template: None,
+            env: BTreeMap::new(),
+            profile: None,
+            all_profiles: false,
+            profiles: BTreeMap::new(),
+            warnings: Vec::new(),
+            replaced_storage: None,
         }
     }
 }
 
 impl CliConfig {
+    /// Open the user config file in the editor, offering to reopen it until
+    /// the saved file is valid
     pub fn edit(&self) -> Result<()> {
         let editor = &self.editor;
         let config_path = get_config_file_path()?;
 
-        let status = std::process::Command::new(editor)
-            .arg(config_path)
-            .status()
-            .context("Failed to open editor")?;
+        loop {
+            let status = std::process::Command::new(editor)
+                .arg(&config_path)
+                .status()
+                .context("Failed to open editor")?;
+
+            if !status.success() {
+                anyhow::bail!("Editor exited with error")
+            }
+
+            match check_config_file(&config_path) {
+                Ok(unknown) => {
+                    for warning in unknown_key_warnings(&config_path, &unknown) {
+                        eprintln!("{}", format!("Warning: {}", warning).yellow());
+                    }
+                    return Ok(());
+                }
+                Err(err) => {
+                    eprintln!("{}", format!("{:#}", err).red());
+
+                    let reopen = dialoguer::Confirm::new()
+                        .with_prompt("Reopen the editor to fix it?")
+                        .default(true)
+                        .interact()
+                        .context("Failed to read confirmation")?;
+
+                    if !reopen {
+                        anyhow::bail!("{} is left invalid", config_path.display());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Every setting as TOML, including the `[fzf]` section left out of
+    /// the config file while it has its defaults
+    pub fn to_table(&self) -> Result<toml::Table> {
+        let mut table = toml::Table::try_from(self).context("Failed to serialize configuration")?;
+
+        if !table.contains_key("fzf") {
+            table.insert(
+                "fzf".to_string(),
+                toml::Value::try_from(&self.fzf).context("Failed to serialize [fzf]")?,
+            );
+        }
+
+        Ok(table)
+    }
+
+    /// Store holding the commands, with the configured backend
+    pub fn command_store(&self) -> Result<CommandStore> {
+        if !self.storage.exists() {
+            fs::create_dir_all(&self.storage).context("Failed to create storage directory")?;
+        }
+
+        if !self.all_profiles || self.profiles.is_empty() {
+            return CommandStore::open(self.storage.clone(), self.storage_backend);
+        }
+
+        let main = self.storage_backend.open(&self.storage)?;
+        let mut profiles = Vec::new();
+
+        let replaced = self
+            .top_level_prefix()
+            .zip(self.replaced_storage.as_ref())
+            .map(|(name, profile)| (name.to_string(), profile));
+
+        // The profile in use is already the main storage
+        for (name, profile) in self
+            .profiles
+            .iter()
+            .map(|(name, profile)| (name.clone(), profile))
+            .chain(replaced)
+        {
+            if profile.storage == self.storage {
+                continue;
+            }
+
+            if !profile.storage.exists() {
+                fs::create_dir_all(&profile.storage).with_context(|| {
+                    format!("Failed to create storage directory for profile {}", name)
+                })?;
+            }
+
+            let backend = profile.storage_backend.unwrap_or(self.storage_backend);
+            profiles.push((name, backend.open(&profile.storage)?));
+        }
+
+        CommandStore::with_storage(
+            self.storage.clone(),
+            Box::new(MultiStorage::new(main, profiles)),
+        )
+    }
+
+    /// Switch to the storage of the selected profile, unless
+    /// `--storage-dir` is given
+    fn apply_profile(&mut self, overrides: &ConfigOverrides) -> Result<()> {
+        let Some(name) = &self.profile else {
+            return Ok(());
+        };
 
-        if !status.success() {
-            anyhow::bail!("Editor exited with error")
+        let Some(profile) = self.profiles.get(name) else {
+            let names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            anyhow::bail!(
+                "{}",
+                if names.is_empty() {
+                    format!("Unknown profile '{}', no profiles are configured", name)
+                } else {
+                    format!(
+                        "Unknown profile '{}', expected one of: {}",
+                        name,
+                        names.join(", ")
+                    )
+                }
+            );
+        };
+
+        if overrides.storage.is_none() {
+            let storage = std::mem::replace(&mut self.storage, profile.storage.clone());
+            let backend = profile.storage_backend.unwrap_or(self.storage_backend);
+
+            self.replaced_storage = Some(Profile {
+                storage,
+                storage_backend: Some(std::mem::replace(&mut self.storage_backend, backend)),
+            });
         }
 
         Ok(())
     }
+
+    /// Profile whose storage is in use, if any
+    pub fn active_profile(&self) -> Option<&str> {
+        self.replaced_storage.as_ref().and(self.profile.as_deref())
+    }
+
+    /// Prefix of the top-level storage's commands when every profile is
+    /// searched while one is selected: `default`, unless a profile has that
+    /// name
+    fn top_level_prefix(&self) -> Option<&'static str> {
+        (self.all_profiles
+            && self.replaced_storage.is_some()
+            && !self.profiles.contains_key(DEFAULT_PROFILE))
+        .then_some(DEFAULT_PROFILE)
+    }
+
+    /// Picker for choosing commands interactively, unless overridden on the
+    /// command line
+    pub fn picker(&self, kind: Option<PickerKind>) -> Result<Box<dyn Picker>> {
+        kind.unwrap_or(self.picker).open(&self.fzf)
+    }
+
+    /// Store holding the secrets referenced by commands
+    pub fn secret_store(&self) -> Result<SecretStore> {
+        Ok(SecretStore::new(
+            &paths::data_dir()?,
+            self.secrets_key.clone(),
+        ))
+    }
+
+    /// Environment variables applied to every command
+    pub fn global_env(&self) -> GlobalEnv {
+        GlobalEnv {
+            vars: self.env.clone(),
+            env_file: self.env_file.clone(),
+        }
+    }
+
+    /// Settings needed to run stored commands
+    pub fn run_context(&self) -> Result<RunContext> {
+        Ok(RunContext {
+            env: self.global_env(),
+            secrets: Some(self.secret_store()?),
+            dangerous_patterns: self.dangerous_patterns.clone(),
+            history: Some(
+                History::new(&paths::state_dir()?)
+                    .for_profile(self.active_profile(), self.top_level_prefix()),
+            ),
+        })
+    }
 }
 
 /// Get the default editor based on environment
 fn get_default_editor() -> String {
-    if let Ok(editor) = std::env::var("EDITOR") {
-        if !editor.is_empty() {
-            return editor;
-        }
+    if let Ok(editor) = std::env::var("EDITOR")
+        && !editor.is_empty()
+    {
+        return editor;
     }
 
     // Check if we're on Windows
@@ -64,97 +327,929 @@ fn get_default_editor() -> String {
 
 /// Get the default storage directory
 fn get_default_storage_dir() -> Result<PathBuf> {
-    let home = home_dir().context("Failed to determine home directory")?;
-    let storage_dir = home.join(DEFAULT_STORAGE);
+    paths::data_dir()
+}
+
+/// Get the config directory path
+pub fn get_config_dir() -> Result<PathBuf> {
+    paths::config_dir()
+}
+
+/// Get the config file path
+pub fn get_config_file_path() -> Result<PathBuf> {
+    let config_dir = get_config_dir()?;
+    Ok(config_dir.join(CONFIG_FILE_NAME))
+}
+
+/// Command-line flags taking part in the configuration
+#[derive(Debug, Default, Clone)]
+pub struct ConfigOverrides {
+    /// Configuration file read on top of every other file (`--config`)
+    pub config_file: Option<PathBuf>,
+    /// Storage directory, overriding every other layer (`--storage-dir`)
+    pub storage: Option<String>,
+    /// Profile to use (`--profile`)
+    pub profile: Option<String>,
+    /// Search every profile at once (`--all-profiles`)
+    pub all_profiles: bool,
+}
+
+/// Where an effective configuration value comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    Default,
+    File(PathBuf),
+    /// Name of the environment variable
+    Environment(String),
+    /// A global command-line flag
+    Flag(&'static str),
+    /// Name of the profile in use
+    Profile(String),
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::File(path) => write!(f, "{}", path.display()),
+            Origin::Environment(name) => write!(f, "{}", name),
+            Origin::Flag(flag) => write!(f, "{}", flag),
+            Origin::Profile(name) => write!(f, "profile {}", name),
+        }
+    }
+}
+
+/// Configuration files, from lowest to highest priority: system-wide, user,
+/// the closest project `.zerp/config.toml` and `--config`. Missing files are
+/// skipped, except for `--config`.
+pub fn config_files(overrides: &ConfigOverrides) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    if cfg!(unix) {
+        files.push(PathBuf::from(SYSTEM_CONFIG_DIR).join(CONFIG_FILE_NAME));
+    }
+
+    files.push(get_config_file_path()?);
+    files.extend(find_project_config());
 
-    // Create directory if it doesn't exist
-    if !storage_dir.exists() {
-        fs::create_dir_all(&storage_dir).context("Failed to create storage directory")?;
+    if let Some(config_file) = &overrides.config_file {
+        if !config_file.is_file() {
+            anyhow::bail!("Config file {} does not exist", config_file.display());
+        }
+        files.push(config_file.clone());
     }
 
-    Ok(storage_dir)
+    Ok(files)
 }
 
-/// Get the config directory path
-fn get_config_dir() -> Result<PathBuf> {
-    let home = home_dir().context("Failed to determine home directory")?;
-    let config_dir = home.join(DEFAULT_STORAGE);
+/// The closest `.zerp/config.toml` in the current directory or its parents,
+/// below the home directory
+fn find_project_config() -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+    let home = dirs::home_dir();
+
+    cwd.ancestors()
+        .take_while(|dir| Some(*dir) != home.as_deref())
+        .map(|dir| dir.join(PROJECT_DIR).join(CONFIG_FILE_NAME))
+        .find(|file| file.is_file())
+}
+
+/// Settings of a project config file, without those it may not change,
+/// which are returned as well
+fn read_project_config(path: &Path) -> Result<(toml::Table, Vec<String>)> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut table: toml::Table = content
+        .parse()
+        .with_context(|| format!("Invalid config file {}", path.display()))?;
+
+    let mut removed = Vec::new();
+    retain_project_settings("", &mut table, &mut removed);
+    Ok((table, removed))
+}
+
+fn retain_project_settings(prefix: &str, table: &mut toml::Table, removed: &mut Vec<String>) {
+    table.retain(|key, value| {
+        let path = if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+
+        if PROJECT_SETTINGS.contains(&path.as_str()) {
+            return true;
+        }
+
+        let nested = PROJECT_SETTINGS
+            .iter()
+            .any(|setting| setting.starts_with(&format!("{}.", path)));
+
+        match value {
+            toml::Value::Table(inner) if nested => {
+                retain_project_settings(&path, inner, removed);
+                true
+            }
+            _ => {
+                removed.push(path);
+                false
+            }
+        }
+    });
+}
 
-    // Create directory if it doesn't exist
-    if !config_dir.exists() {
-        fs::create_dir_all(&config_dir).context("Failed to create config directory")?;
+/// Every layer below the flags, from lowest to highest priority: the config
+/// files and `ZERP_*` environment variables (`__` separates nested keys, e.g.
+/// `ZERP_FZF__HEIGHT`)
+fn sources(overrides: &ConfigOverrides) -> Result<Vec<(Origin, Box<dyn Source + Send + Sync>)>> {
+    let mut sources: Vec<(Origin, Box<dyn Source + Send + Sync>)> = Vec::new();
+
+    let project = find_project_config();
+
+    for file in config_files(overrides)? {
+        let source: Box<dyn Source + Send + Sync> = if Some(&file) == project.as_ref() {
+            let (table, _) = read_project_config(&file)?;
+            let content = toml::to_string(&table).context("Failed to serialize config")?;
+            Box::new(File::from_str(&content, FileFormat::Toml))
+        } else {
+            Box::new(File::from(file.clone()).required(false))
+        };
+
+        sources.push((Origin::File(file), source));
     }
 
-    Ok(config_dir)
+    sources.push((
+        Origin::Environment(ENV_PREFIX.to_string()),
+        Box::new(
+            Environment::with_prefix(ENV_PREFIX)
+                .prefix_separator("_")
+                .separator("__")
+                .try_parsing(true)
+                .list_separator(",")
+                .with_list_parse_key("dangerous_patterns"),
+        ),
+    ));
+
+    Ok(sources)
 }
 
-/// Get the config file path
-pub fn get_config_file_path() -> Result<PathBuf> {
-    let config_dir = get_config_dir()?;
-    Ok(config_dir.join(CONFIG_FILE_NAME))
+/// Merge every layer, with the flags on top
+fn build_layers(overrides: &ConfigOverrides) -> Result<Config> {
+    let sources = sources(overrides)?
+        .into_iter()
+        .map(|(_, source)| source)
+        .collect::<Vec<_>>();
+    let mut builder = Config::builder().add_source(sources);
+
+    if let Some(storage) = &overrides.storage {
+        builder = builder.set_override("storage", storage.as_str())?;
+    }
+
+    if let Some(profile) = &overrides.profile {
+        builder = builder.set_override("profile", profile.as_str())?;
+    }
+
+    if overrides.all_profiles {
+        builder = builder.set_override("all_profiles", true)?;
+    }
+
+    builder.build().context("Failed to build configuration")
 }
 
-/// Load configuration from file or create default
+/// Load the configuration, with defaults for every missing setting
 pub fn load_config() -> Result<CliConfig> {
+    load_config_with(&ConfigOverrides::default())
+}
+
+/// Create the user config file when it doesn't exist yet, with every
+/// default commented out so that they don't hide the system-wide file.
+/// Returns its path when it was created.
+pub fn create_user_config() -> Result<Option<PathBuf>> {
     let config_path = get_config_file_path()?;
-    let mut app_config = CliConfig::default();
 
     if config_path.exists() {
-        let config = Config::builder()
-            .add_source(File::from(config_path.clone()))
-            .build()
-            .context("Failed to build configuration")?;
+        return Ok(None);
+    }
+
+    let defaults =
+        toml::to_string(&CliConfig::default().to_table()?).context("Failed to serialize config")?;
+    let mut content = String::from(
+        "# Uncomment a setting to change it. `zerp config list` shows every setting.\n\n",
+    );
+
+    for line in defaults.lines() {
+        if !line.is_empty() {
+            content.push_str("# ");
+            content.push_str(line);
+        }
+        content.push('\n');
+    }
+
+    fs::write(&config_path, content).context("Failed to write config file")?;
+    Ok(Some(config_path))
+}
+
+/// Load the layered configuration, with command-line flags on top. Unknown
+/// settings are ignored and listed in `warnings`.
+pub fn load_config_with(overrides: &ConfigOverrides) -> Result<CliConfig> {
+    let mut warnings = Vec::new();
 
-        if let Ok(editor) = config.get_string("editor") {
-            app_config.editor = editor;
+    let project = find_project_config();
+
+    for file in config_files(overrides)? {
+        if file.is_file() {
+            warnings.extend(unknown_key_warnings(&file, &check_config_file(&file)?));
         }
 
-        if let Ok(storage) = config.get_string("storage") {
-            app_config.storage = PathBuf::from(shellexpand::tilde(&storage).into_owned());
+        if Some(&file) == project.as_ref() {
+            let (_, removed) = read_project_config(&file)?;
+            warnings.extend(removed.iter().map(|key| {
+                format!(
+                    "'{}' in {} is ignored, project config files can only set {}",
+                    key,
+                    file.display(),
+                    PROJECT_SETTINGS.join(", ")
+                )
+            }));
         }
-    } else {
-        save_config(&app_config)?;
-        println!("Created default config at: {}", config_path.display());
     }
 
-    // Ensure storage directory exists
-    if !app_config.storage.exists() {
-        fs::create_dir_all(&app_config.storage).context("Failed to create storage directory")?;
+    let config = build_layers(overrides)?;
+    let mut app_config: CliConfig = config
+        .try_deserialize()
+        .map_err(|err| anyhow::anyhow!("Invalid configuration: {}", err))?;
+
+    app_config.warnings = warnings;
+
+    app_config.storage = expand_tilde(&app_config.storage);
+    app_config.env_file = app_config.env_file.as_deref().map(expand_tilde);
+    app_config.secrets_key = app_config.secrets_key.as_deref().map(expand_tilde);
+
+    for profile in app_config.profiles.values_mut() {
+        profile.storage = expand_tilde(&profile.storage);
     }
 
+    app_config.apply_profile(overrides)?;
+
     Ok(app_config)
 }
 
+fn expand_tilde(path: &Path) -> PathBuf {
+    PathBuf::from(shellexpand::tilde(&path.to_string_lossy()).into_owned())
+}
+
+/// Check that a config file is valid TOML whose values have the right types,
+/// with the line and column of the first error. Returns the unknown keys,
+/// which are ignored.
+pub fn check_config_file(path: &Path) -> Result<Vec<String>> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+
+    toml::from_str::<CliConfig>(&content)
+        .map_err(|err| anyhow::anyhow!("Invalid config file {}:\n{}", path.display(), err))?;
+
+    let table: toml::Table = content
+        .parse()
+        .with_context(|| format!("Invalid config file {}", path.display()))?;
+
+    let mut unknown = Vec::new();
+    find_unknown_keys("", &table, &mut unknown);
+    Ok(unknown)
+}
+
+fn unknown_key_warnings(path: &Path, keys: &[String]) -> Vec<String> {
+    keys.iter()
+        .map(|key| format!("unknown setting '{}' in {}, ignored", key, path.display()))
+        .collect()
+}
+
+fn find_unknown_keys(prefix: &str, table: &toml::Table, unknown: &mut Vec<String>) {
+    for (key, value) in table {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+
+        if lookup_kind(&path).is_some() {
+            continue;
+        }
+
+        let has_settings = SETTINGS
+            .iter()
+            .any(|(name, _)| name.starts_with(&format!("{}.", path)))
+            || path == "profiles"
+            || path
+                .strip_prefix("profiles.")
+                .is_some_and(|name| !name.contains('.'));
+
+        match value {
+            toml::Value::Table(inner) if has_settings => find_unknown_keys(&path, inner, unknown),
+            _ => unknown.push(path),
+        }
+    }
+}
+
+/// Every effective setting as a key path (e.g. `fzf.height`), its value in
+/// TOML syntax and where it was set
+pub fn effective_values(
+    app_config: &CliConfig,
+    overrides: &ConfigOverrides,
+) -> Result<Vec<(String, String, Origin)>> {
+    // Each layer on its own, highest priority first
+    let mut layers = Vec::new();
+    for (origin, source) in sources(overrides)?.into_iter().rev() {
+        let layer = Config::builder()
+            .add_source(vec![source])
+            .build()
+            .with_context(|| format!("Failed to read {}", origin))?;
+        layers.push((origin, layer));
+    }
+
+    let effective = toml::Value::Table(app_config.to_table()?);
+    let mut values = Vec::new();
+    flatten("", &effective, &mut values);
+
+    Ok(values
+        .into_iter()
+        .map(|(key, value)| {
+            let layer = layers
+                .iter()
+                .find(|(_, layer)| layer.get::<config::Value>(&key).is_ok());
+
+            // Only set when a profile provides the storage
+            let profile = app_config
+                .profile
+                .as_ref()
+                .filter(|_| overrides.storage.is_none());
+
+            let origin = match layer {
+                _ if key == "storage" && overrides.storage.is_some() => {
+                    Origin::Flag("--storage-dir")
+                }
+                _ if key == "profile" && overrides.profile.is_some() => Origin::Flag("--profile"),
+                _ if key == "all_profiles" && overrides.all_profiles => {
+                    Origin::Flag("--all-profiles")
+                }
+                _ if key == "storage" && profile.is_some() => {
+                    Origin::Profile(profile.cloned().unwrap_or_default())
+                }
+                _ if key == "storage_backend"
+                    && profile.is_some_and(|name| {
+                        app_config.profiles[name].storage_backend.is_some()
+                    }) =>
+                {
+                    Origin::Profile(profile.cloned().unwrap_or_default())
+                }
+                Some((Origin::Environment(_), _)) => Origin::Environment(format!(
+                    "{}_{}",
+                    ENV_PREFIX,
+                    key.replace('.', "__").to_uppercase()
+                )),
+                Some((origin, _)) => origin.clone(),
+                None => Origin::Default,
+            };
+
+            (key, value.to_string(), origin)
+        })
+        .collect())
+}
+
+/// Collect the leaves of a TOML table with their dotted key paths
+fn flatten(prefix: &str, value: &toml::Value, values: &mut Vec<(String, toml::Value)>) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, value) in table {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(&path, value, values);
+            }
+        }
+        _ => values.push((prefix.to_string(), value.clone())),
+    }
+}
+
 pub fn save_config(config: &CliConfig) -> Result<()> {
     let config_path = get_config_file_path()?;
     let config_str = toml::to_string(config).context("Failed to serialize config")?;
 
-    if let Some(parent) = config_path.parent() {
-        if !parent.exists() {
-            fs::create_dir_all(parent).context("Failed to create config directory")?;
-        }
+    if let Some(parent) = config_path.parent()
+        && !parent.exists()
+    {
+        fs::create_dir_all(parent).context("Failed to create config directory")?;
     }
 
     fs::write(config_path, config_str).context("Failed to write config file")?;
     Ok(())
 }
 
-pub fn set_editor(editor: &str) -> Result<()> {
-    let mut config = load_config()?;
-    config.editor = editor.to_string();
-    save_config(&config)?;
+/// Change keys in the user config file only, so values from other layers
+/// are not copied into it. The file is edited in place, keeping its comments
+/// and layout.
+fn update_user_config(f: impl FnOnce(&mut DocumentMut) -> Result<()>) -> Result<()> {
+    let config_path = get_config_file_path()?;
+
+    let mut document = if config_path.exists() {
+        let content = fs::read_to_string(&config_path).context("Failed to read config file")?;
+        content
+            .parse::<DocumentMut>()
+            .with_context(|| format!("Failed to parse {}", config_path.display()))?
+    } else {
+        DocumentMut::new()
+    };
+
+    f(&mut document)?;
+
+    fs::write(config_path, document.to_string()).context("Failed to write config file")?;
+    Ok(())
+}
+
+/// Set `key` in `table`, keeping the comments around a previous value
+fn set_value(table: &mut dyn TableLike, key: &str, mut value: toml_edit::Value) {
+    match table.get_mut(key) {
+        Some(item) => {
+            if let Some(previous) = item.as_value() {
+                *value.decor_mut() = previous.decor().clone();
+            }
+            *item = Item::Value(value);
+        }
+        None => {
+            table.insert(key, Item::Value(value));
+        }
+    }
+}
+
+/// Set the value at `parents.last`, creating the parent tables it needs
+fn insert_key(
+    document: &mut DocumentMut,
+    parents: &[&str],
+    last: &str,
+    value: toml_edit::Value,
+) -> Result<()> {
+    let mut current: &mut dyn TableLike = document.as_table_mut();
+
+    for part in parents {
+        let entry = current.entry(part).or_insert_with(|| {
+            // Only shows a header once it holds values of its own
+            let mut table = toml_edit::Table::new();
+            table.set_implicit(true);
+            Item::Table(table)
+        });
+
+        current = entry
+            .as_table_like_mut()
+            .with_context(|| format!("{} is not a table", part))?;
+    }
+
+    set_value(current, last, value);
     Ok(())
 }
 
+pub fn set_editor(editor: &str) -> Result<()> {
+    update_user_config(|document| {
+        set_value(document.as_table_mut(), "editor", editor.into());
+        Ok(())
+    })
+}
+
 pub fn set_storage(storage: &str) -> Result<()> {
-    let mut config = load_config()?;
     let expanded_path = shellexpand::tilde(storage).into_owned();
-    config.storage = PathBuf::from(expanded_path);
 
-    if !config.storage.exists() {
-        fs::create_dir_all(&config.storage).context("Failed to create storage directory")?;
+    if !Path::new(&expanded_path).exists() {
+        fs::create_dir_all(&expanded_path).context("Failed to create storage directory")?;
     }
 
-    save_config(&config)?;
-    Ok(())
+    update_user_config(|document| {
+        set_value(document.as_table_mut(), "storage", expanded_path.into());
+        Ok(())
+    })
+}
+
+/// Set the backend of the top-level storage, or of the given profile's
+/// storage, which must be defined in the user config file
+pub fn set_storage_backend(backend: Backend, profile: Option<&str>) -> Result<()> {
+    update_user_config(|document| {
+        let table: &mut dyn TableLike = match profile {
+            Some(name) => document
+                .get_mut("profiles")
+                .and_then(|profiles| profiles.get_mut(name))
+                .and_then(Item::as_table_like_mut)
+                .with_context(|| {
+                    format!(
+                        "Profile '{}' is not defined in the user config file, set profiles.{}.storage_backend where it is",
+                        name, name
+                    )
+                })?,
+            None => document.as_table_mut(),
+        };
+
+        set_value(table, "storage_backend", backend.to_string().into());
+        Ok(())
+    })
+}
+
+/// Type of a setting's value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    String,
+    Bool,
+    List,
+    /// Free-form keys with string values, set one key at a time
+    Table,
+}
+
+impl fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ValueKind::String => "a string",
+            ValueKind::Bool => "true or false",
+            ValueKind::List => "a list",
+            ValueKind::Table => "a table",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Every key path of `CliConfig` with the type of its value, for `zerp
+/// config get/set/unset`. Values are checked against `CliConfig` itself
+/// before being saved, so a stale entry cannot write an invalid config.
+pub const SETTINGS: [(&str, ValueKind); 20] = [
+    ("editor", ValueKind::String),
+    ("storage", ValueKind::String),
+    ("storage_backend", ValueKind::String),
+    ("picker", ValueKind::String),
+    ("fzf.height", ValueKind::String),
+    ("fzf.layout", ValueKind::String),
+    ("fzf.border", ValueKind::String),
+    ("fzf.pointer", ValueKind::String),
+    ("fzf.prompt", ValueKind::String),
+    ("fzf.preview_window", ValueKind::String),
+    ("fzf.preview", ValueKind::String),
+    ("fzf.args", ValueKind::List),
+    ("fzf.bind", ValueKind::Table),
+    ("env_file", ValueKind::String),
+    ("secrets_key", ValueKind::String),
+    ("dangerous_patterns", ValueKind::List),
+    ("lint_on_save", ValueKind::Bool),
+    ("default_template", ValueKind::String),
+    ("profile", ValueKind::String),
+    ("all_profiles", ValueKind::Bool),
+];
+
+/// Settings of each `[profiles.<name>]` section
+const PROFILE_SETTINGS: [(&str, ValueKind); 2] = [
+    ("storage", ValueKind::String),
+    ("storage_backend", ValueKind::String),
+];
+
+/// Settings that are tables of their own rather than one key of `SETTINGS`
+const TABLE_SETTINGS: [&str; 1] = ["env"];
+
+/// Type of the value at `key`, e.g. `fzf.height` or `env.NAME`
+fn lookup_kind(key: &str) -> Option<ValueKind> {
+    if let Some((_, kind)) = SETTINGS.iter().find(|(name, _)| *name == key) {
+        return Some(*kind);
+    }
+
+    if TABLE_SETTINGS.contains(&key) {
+        return Some(ValueKind::Table);
+    }
+
+    if let Some((_, setting)) = key
+        .strip_prefix("profiles.")
+        .and_then(|rest| rest.split_once('.'))
+    {
+        return PROFILE_SETTINGS
+            .iter()
+            .find(|(name, _)| *name == setting)
+            .map(|(_, kind)| *kind);
+    }
+
+    // A key inside a table, like `env.NAME` or `fzf.bind.ctrl-x`
+    SETTINGS
+        .iter()
+        .filter(|(_, kind)| *kind == ValueKind::Table)
+        .map(|(name, _)| *name)
+        .chain(TABLE_SETTINGS)
+        .any(|table| {
+            key.strip_prefix(table)
+                .and_then(|rest| rest.strip_prefix('.'))
+                .is_some_and(|rest| !rest.is_empty())
+        })
+        .then_some(ValueKind::String)
+}
+
+/// Type of the value at `key`, failing for unknown settings
+pub fn setting_kind(key: &str) -> Result<ValueKind> {
+    lookup_kind(key).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Unknown setting '{}', see `zerp config list` for the settings",
+            key
+        )
+    })
+}
+
+/// Parse a value given on the command line for a setting of the given kind.
+/// Lists are written as TOML arrays or comma-separated.
+fn parse_setting(key: &str, kind: ValueKind, raw: &str) -> Result<toml::Value> {
+    match kind {
+        ValueKind::String => Ok(raw.into()),
+        ValueKind::Bool => match raw {
+            "true" => Ok(true.into()),
+            "false" => Ok(false.into()),
+            _ => anyhow::bail!("Invalid value for {}: expected {}", key, kind),
+        },
+        ValueKind::List if raw.trim_start().starts_with('[') => {
+            let document = format!("value = {}", raw);
+            let table: toml::Table = document
+                .parse()
+                .with_context(|| format!("Invalid list for {}", key))?;
+            Ok(table["value"].clone())
+        }
+        ValueKind::List => Ok(toml::Value::Array(
+            raw.split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(toml::Value::from)
+                .collect(),
+        )),
+        ValueKind::Table => anyhow::bail!(
+            "{} is a table, set its keys one by one, like {}.NAME",
+            key,
+            key
+        ),
+    }
+}
+
+/// Make sure a user config document still makes a valid `CliConfig`
+fn check_user_config(document: &DocumentMut) -> Result<()> {
+    toml::from_str::<CliConfig>(&document.to_string())
+        .map(|_| ())
+        .map_err(|err| anyhow::anyhow!("Invalid setting: {}", err.message()))
+}
+
+/// Effective value of a setting, or `None` when it is not set
+pub fn get_setting(app_config: &CliConfig, key: &str) -> Result<Option<toml::Value>> {
+    setting_kind(key)?;
+
+    let mut value = toml::Value::Table(app_config.to_table()?);
+
+    let (parents, last) = split_key(key);
+
+    for part in parents.into_iter().chain([last]) {
+        match value.get(part) {
+            Some(inner) => value = inner.clone(),
+            None => return Ok(None),
+        }
+    }
+
+    Ok(Some(value))
+}
+
+/// Set a setting in the user config file, returning the value written
+pub fn set_setting(key: &str, raw: &str) -> Result<toml::Value> {
+    let kind = setting_kind(key)?;
+    let value = parse_setting(key, kind, raw)?;
+    let edited = value
+        .to_string()
+        .parse::<toml_edit::Value>()
+        .context("Failed to serialize value")?;
+
+    update_user_config(|document| {
+        let (parents, last) = split_key(key);
+        insert_key(document, &parents, last, edited)?;
+        check_user_config(document)
+    })?;
+
+    Ok(value)
+}
+
+/// Remove a setting from the user config file, so the other layers or the
+/// default apply. Returns whether it was set.
+pub fn unset_setting(key: &str) -> Result<bool> {
+    setting_kind(key)?;
+    let mut removed = false;
+
+    update_user_config(|document| {
+        let (parents, last) = split_key(key);
+        removed = remove_key(document.as_table_mut(), &parents, last);
+        Ok(())
+    })?;
+
+    Ok(removed)
+}
+
+/// Remove `last` from the table at `parents`, dropping the tables it
+/// leaves empty
+fn remove_key(table: &mut dyn TableLike, parents: &[&str], last: &str) -> bool {
+    match parents.split_first() {
+        None => table.remove(last).is_some(),
+        Some((first, rest)) => {
+            let Some(inner) = table.get_mut(first).and_then(Item::as_table_like_mut) else {
+                return false;
+            };
+
+            let removed = remove_key(inner, rest, last);
+            if inner.is_empty() {
+                table.remove(first);
+            }
+            removed
+        }
+    }
+}
+
+/// Split `a.b.c` into the parent tables `[a, b]` and the key `c`. Keys inside
+/// free-form tables may contain dots, e.g. `env.A.B` is the variable `A.B`.
+fn split_key(key: &str) -> (Vec<&str>, &str) {
+    let table = SETTINGS
+        .iter()
+        .filter(|(_, kind)| *kind == ValueKind::Table)
+        .map(|(name, _)| *name)
+        .chain(TABLE_SETTINGS)
+        .find(|table| key.starts_with(&format!("{}.", table)));
+
+    match table {
+        Some(table) => (table.split('.').collect(), &key[table.len() + 1..]),
+        None => {
+            let mut parts: Vec<&str> = key.split('.').collect();
+            let last = parts.pop().unwrap_or_default();
+            (parts, last)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::picker::Action;
+
+    /// Every setting of a config with all optional values set, stopping at
+    /// free-form tables
+    fn settings(prefix: &str, table: &toml::Table, found: &mut Vec<(String, toml::Value)>) {
+        for (key, value) in table {
+            let path = if prefix.is_empty() {
+                key.to_string()
+            } else {
+                format!("{}.{}", prefix, key)
+            };
+
+            match value {
+                toml::Value::Table(inner) if lookup_kind(&path).is_none() && path != "profiles" => {
+                    settings(&path, inner, found)
+                }
+                _ => found.push((path, value.clone())),
+            }
+        }
+    }
+
+    #[test]
+    fn settings_match_cli_config() {
+        let mut config = CliConfig {
+            env_file: Some(PathBuf::from("env")),
+            secrets_key: Some(PathBuf::from("key")),
+            default_template: Some("template".to_string()),
+            profile: Some("work".to_string()),
+            ..CliConfig::default()
+        };
+        config.env.insert("NAME".to_string(), "value".to_string());
+        config.profiles.insert(
+            "work".to_string(),
+            Profile {
+                storage: PathBuf::from("work"),
+                storage_backend: Some(Backend::default()),
+            },
+        );
+        config.fzf.preview = Some("cat {file}".to_string());
+        config.fzf.args = vec!["--cycle".to_string()];
+        config.fzf.bind.insert("ctrl-e".to_string(), Action::Edit);
+
+        let mut found = Vec::new();
+        settings("", &config.to_table().unwrap(), &mut found);
+
+        let mut keys: Vec<&str> = found
+            .iter()
+            .map(|(key, _)| key.as_str())
+            .filter(|key| *key != "profiles")
+            .collect();
+        let mut expected: Vec<&str> = SETTINGS
+            .iter()
+            .map(|(key, _)| *key)
+            .chain(TABLE_SETTINGS)
+            .collect();
+        keys.sort();
+        expected.sort();
+        assert_eq!(keys, expected);
+
+        for (key, value) in &found {
+            let matches = match lookup_kind(key) {
+                Some(ValueKind::String) => value.is_str(),
+                Some(ValueKind::Bool) => value.is_bool(),
+                Some(ValueKind::List) => value.is_array(),
+                Some(ValueKind::Table) | None => value.is_table(),
+            };
+            assert!(matches, "{} has the wrong kind: {}", key, value);
+        }
+
+        let profile = config.profiles["work"].clone();
+        let profile = toml::Table::try_from(profile).unwrap();
+        let mut keys: Vec<&str> = profile.keys().map(String::as_str).collect();
+        let mut expected: Vec<&str> = PROFILE_SETTINGS.iter().map(|(key, _)| *key).collect();
+        keys.sort();
+        expected.sort();
+        assert_eq!(keys, expected);
+    }
+
+    #[test]
+    fn split_keys_inside_tables() {
+        assert_eq!(split_key("fzf.height"), (vec!["fzf"], "height"));
+        assert_eq!(split_key("env.A.B"), (vec!["env"], "A.B"));
+        assert_eq!(
+            split_key("fzf.bind.ctrl-x"),
+            (vec!["fzf", "bind"], "ctrl-x")
+        );
+    }
+
+    #[test]
+    fn remove_key_with_dots() {
+        let mut document: DocumentMut = "editor = \"vim\"\n[env]\n\"A.B\" = \"1\"\n"
+            .parse()
+            .unwrap();
+        let (parents, last) = split_key("env.A.B");
+
+        assert!(remove_key(document.as_table_mut(), &parents, last));
+        assert!(!document.contains_key("env"));
+        assert!(document.contains_key("editor"));
+    }
+
+    const COMMENTED: &str = "\
+# My settings
+editor = \"vim\" # the one true editor
+
+# Finder
+[fzf]
+height = \"40%\"
+";
+
+    #[test]
+    fn insert_key_keeps_comments() {
+        let mut document: DocumentMut = COMMENTED.parse().unwrap();
+
+        insert_key(&mut document, &[], "editor", "hx".into()).unwrap();
+        insert_key(&mut document, &["fzf"], "prompt", "> ".into()).unwrap();
+        insert_key(&mut document, &["fzf", "bind"], "ctrl-e", "edit".into()).unwrap();
+        insert_key(
+            &mut document,
+            &["profiles", "work"],
+            "storage",
+            "~/w".into(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            document.to_string(),
+            "\
+# My settings
+editor = \"hx\" # the one true editor
+
+# Finder
+[fzf]
+height = \"40%\"
+prompt = \"> \"
+
+[fzf.bind]
+ctrl-e = \"edit\"
+
+[profiles.work]
+storage = \"~/w\"
+"
+        );
+        check_user_config(&document).unwrap();
+    }
+
+    #[test]
+    fn insert_key_into_inline_table() {
+        let mut document: DocumentMut = "fzf = { height = \"40%\" }\n".parse().unwrap();
+
+        insert_key(&mut document, &["fzf"], "prompt", "> ".into()).unwrap();
+        let table: toml::Table = document.to_string().parse().unwrap();
+        assert_eq!(table["fzf"]["height"].as_str(), Some("40%"));
+        assert_eq!(table["fzf"]["prompt"].as_str(), Some("> "));
+
+        let err = insert_key(&mut document, &["fzf", "height"], "x", "y".into()).unwrap_err();
+        assert_eq!(err.to_string(), "height is not a table");
+    }
+
+    #[test]
+    fn remove_key_keeps_comments() {
+        let mut document: DocumentMut = COMMENTED.parse().unwrap();
+
+        assert!(remove_key(document.as_table_mut(), &["fzf"], "height"));
+        assert!(!remove_key(document.as_table_mut(), &[], "picker"));
+        assert_eq!(
+            document.to_string(),
+            "# My settings\neditor = \"vim\" # the one true editor\n"
+        );
+    }
 }
@@ -0,0 +1,208 @@
+use zerp::metadata::Metadata;
+
+const HEADER_START: &str = "# --- zerp ---";
+const HEADER_END: &str = "# --- lines above are saved as metadata ---";
+
+/// A command being written in the editor: its script plus the metadata shown
+/// in a comment header at the top of the buffer.
+#[derive(Debug, Clone)]
+pub struct Draft {
+    pub name: String,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    pub body: String,
+}
+
+impl Draft {
+    pub fn new(name: &str, metadata: &Metadata, body: &str) -> Self {
+        Draft {
+            name: name.to_string(),
+            description: metadata.description.clone(),
+            tags: metadata.tags.clone(),
+            body: body.to_string(),
+        }
+    }
+
+    /// The editor buffer: header followed by the script
+    pub fn render(&self) -> String {
+        format!(
+            "{}\n# name: {}\n# description: {}\n# tags: {}\n{}\n\n{}",
+            HEADER_START,
+            self.name,
+            self.description.as_deref().unwrap_or_default(),
+            self.tags.join(", "),
+            HEADER_END,
+            self.body
+        )
+    }
+
+    /// Read an edited buffer back. Fields missing from the header keep their
+    /// previous values, and a buffer without a header is all script.
+    pub fn parse(&self, buffer: &str) -> Draft {
+        let mut draft = self.clone();
+        let mut lines = buffer.split_inclusive('\n');
+
+        let Some(first) = lines.next().filter(|line| line.trim() == HEADER_START) else {
+            draft.body = buffer.to_string();
+            return draft;
+        };
+
+        let mut consumed = first.len();
+
+        // The header ends at its closing line, or at the first line that is
+        // not a comment when that was deleted
+        for line in lines {
+            if !line.trim_start().starts_with('#') {
+                break;
+            }
+
+            consumed += line.len();
+
+            if line.trim_start().starts_with("# ---") {
+                break;
+            }
+
+            let Some((key, value)) = line.trim_start_matches('#').split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+
+            match key.trim() {
+                "name" if !value.is_empty() => draft.name = value.to_string(),
+                "description" => draft.description = (!value.is_empty()).then(|| value.to_string()),
+                "tags" => {
+                    draft.tags = value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|tag| !tag.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                }
+                _ => {}
+            }
+        }
+
+        // Drop the blank line separating the header from the script
+        let rest = &buffer[consumed..];
+        draft.body = rest
+            .strip_prefix("\r\n")
+            .or_else(|| rest.strip_prefix('\n'))
+            .unwrap_or(rest)
+            .to_string();
+        draft
+    }
+
+    /// Copy the header fields into the command's metadata
+    pub fn apply_to(&self, metadata: &mut Metadata) {
+        metadata.description = self.description.clone();
+        metadata.tags = self.tags.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draft() -> Draft {
+        Draft {
+            name: "deploy".to_string(),
+            description: Some("Ship it".to_string()),
+            tags: vec!["ops".to_string(), "prod".to_string()],
+            body: "set -e\n./deploy.sh\n".to_string(),
+        }
+    }
+
+    fn assert_same(parsed: &Draft, expected: &Draft) {
+        assert_eq!(parsed.name, expected.name);
+        assert_eq!(parsed.description, expected.description);
+        assert_eq!(parsed.tags, expected.tags);
+        assert_eq!(parsed.body, expected.body);
+    }
+
+    #[test]
+    fn render_then_parse_round_trips() {
+        let draft = draft();
+        assert_same(&draft.parse(&draft.render()), &draft);
+    }
+
+    #[test]
+    fn edited_fields_are_read_back() {
+        let buffer = draft()
+            .render()
+            .replace("# name: deploy", "# name: release")
+            .replace("# tags: ops, prod", "# tags: ci,, web ");
+
+        let parsed = draft().parse(&buffer);
+        assert_eq!(parsed.name, "release");
+        assert_eq!(parsed.tags, ["ci", "web"]);
+    }
+
+    #[test]
+    fn blank_description_and_tags() {
+        let draft = Draft {
+            description: None,
+            tags: Vec::new(),
+            ..draft()
+        };
+
+        let parsed = draft.parse(&draft.render());
+        assert_same(&parsed, &draft);
+
+        let cleared = self::draft().parse(&draft.render());
+        assert_eq!(cleared.description, None);
+        assert!(cleared.tags.is_empty());
+    }
+
+    #[test]
+    fn blank_name_keeps_the_previous_one() {
+        let buffer = draft().render().replace("# name: deploy", "# name:");
+        assert_eq!(draft().parse(&buffer).name, "deploy");
+    }
+
+    #[test]
+    fn buffer_without_header_is_all_script() {
+        let buffer = "echo one\n# name: other\n";
+        let parsed = draft().parse(buffer);
+
+        assert_eq!(parsed.body, buffer);
+        assert_eq!(parsed.name, "deploy");
+        assert_eq!(parsed.tags, ["ops", "prod"]);
+    }
+
+    #[test]
+    fn header_without_closing_line_ends_at_the_script() {
+        let buffer = format!(
+            "{}\n# name: deploy\n# description: Changed\n\necho hi\n",
+            HEADER_START
+        );
+
+        let parsed = draft().parse(&buffer);
+        assert_eq!(parsed.description.as_deref(), Some("Changed"));
+        assert_eq!(parsed.body, "echo hi\n");
+    }
+
+    #[test]
+    fn script_starting_with_comments() {
+        let draft = Draft {
+            body: "#!/bin/bash\n# name: not metadata\n# --- nor this\necho hi\n".to_string(),
+            ..draft()
+        };
+
+        assert_same(&draft.parse(&draft.render()), &draft);
+    }
+
+    #[test]
+    fn crlf_buffer() {
+        let draft = draft();
+        let buffer = draft
+            .render()
+            .replace("Ship it", "Ship it now")
+            .replace('\n', "\r\n");
+
+        let parsed = draft.parse(&buffer);
+        assert_eq!(parsed.name, "deploy");
+        assert_eq!(parsed.description.as_deref(), Some("Ship it now"));
+        assert_eq!(parsed.tags, ["ops", "prod"]);
+        assert_eq!(parsed.body, "set -e\r\n./deploy.sh\r\n");
+    }
+}
@@ -0,0 +1,169 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::metadata::{self, Metadata};
+
+/// Name of the dotenv file loaded from a project's `.zerp` directory
+const PROJECT_ENV_FILE: &str = ".env";
+
+/// Environment variables configured globally in `config.toml`
+#[derive(Debug, Default, Clone)]
+pub struct GlobalEnv {
+    pub vars: BTreeMap<String, String>,
+    pub env_file: Option<PathBuf>,
+}
+
+/// Merge the environment for a command, from lowest to highest precedence:
+///
+/// 1. the global `env_file` from `config.toml`
+/// 2. the global `[env]` table from `config.toml`
+/// 3. the project's `.zerp/.env` file
+/// 4. the command's `env_file` metadata
+/// 5. the command's `[env]` metadata table
+/// 6. `-e KEY=VAL` overrides
+///
+/// Variables inherited from the calling shell sit below all of these.
+pub fn resolve_env(
+    global: &GlobalEnv,
+    metadata: &Metadata,
+    cwd: Option<&Path>,
+    overrides: &[(String, String)],
+) -> Result<BTreeMap<String, String>> {
+    let project = metadata::find_project_root();
+    merge_env(global, metadata, cwd, overrides, project.as_deref())
+}
+
+fn merge_env(
+    global: &GlobalEnv,
+    metadata: &Metadata,
+    cwd: Option<&Path>,
+    overrides: &[(String, String)],
+    project: Option<&Path>,
+) -> Result<BTreeMap<String, String>> {
+    let mut env = BTreeMap::new();
+
+    if let Some(path) = &global.env_file {
+        load_env_file(&mut env, path)?;
+    }
+
+    env.extend(global.vars.clone());
+
+    if let Some(root) = project {
+        let path = root.join(".zerp").join(PROJECT_ENV_FILE);
+        if path.is_file() {
+            load_env_file(&mut env, &path)?;
+        }
+    }
+
+    if let Some(env_file) = &metadata.env_file {
+        let path = PathBuf::from(shellexpand::tilde(env_file).into_owned());
+        let path = match cwd {
+            Some(cwd) if path.is_relative() => cwd.join(path),
+            _ => path,
+        };
+        load_env_file(&mut env, &path)?;
+    }
+
+    env.extend(metadata.env.clone());
+    env.extend(overrides.iter().cloned());
+
+    Ok(env)
+}
+
+fn load_env_file(env: &mut BTreeMap<String, String>, path: &Path) -> Result<()> {
+    let entries = dotenvy::from_path_iter(path)
+        .with_context(|| format!("Failed to read env file {}", path.display()))?;
+
+    for entry in entries {
+        let (key, value) =
+            entry.with_context(|| format!("Failed to parse env file {}", path.display()))?;
+        env.insert(key, value);
+    }
+
+    Ok(())
+}
+
+/// Parse a `KEY=VAL` pair given on the command line
+pub fn parse_key_val(s: &str) -> std::result::Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("invalid KEY=VAL pair: '{}'", s)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const LEVELS: [&str; 6] = [
+        "global_file",
+        "global",
+        "project",
+        "command_file",
+        "command",
+        "override",
+    ];
+
+    /// `KEY<n>=<value>` for every key set at level `n` or above, so the value
+    /// of `KEY<n>` tells which of the levels up to `n` won
+    fn vars(level: usize) -> Vec<(String, String)> {
+        (level..LEVELS.len())
+            .map(|key| (format!("KEY{}", key), LEVELS[level].to_string()))
+            .collect()
+    }
+
+    fn write_env_file(path: &Path, level: usize) {
+        let content: String = vars(level)
+            .into_iter()
+            .map(|(key, value)| format!("{}={}\n", key, value))
+            .collect();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn every_level_overrides_the_ones_below() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("project");
+        fs::create_dir_all(project.join(".zerp")).unwrap();
+
+        write_env_file(&dir.path().join("global.env"), 0);
+        write_env_file(&project.join(".zerp").join(PROJECT_ENV_FILE), 2);
+        write_env_file(&dir.path().join("command.env"), 3);
+
+        let global = GlobalEnv {
+            vars: vars(1).into_iter().collect(),
+            env_file: Some(dir.path().join("global.env")),
+        };
+        let metadata = Metadata {
+            env_file: Some("command.env".to_string()),
+            env: vars(4).into_iter().collect(),
+            ..Metadata::default()
+        };
+
+        let env = merge_env(
+            &global,
+            &metadata,
+            Some(dir.path()),
+            &vars(5),
+            Some(&project),
+        )
+        .unwrap();
+
+        for (level, winner) in LEVELS.iter().enumerate() {
+            assert_eq!(env[&format!("KEY{}", level)], *winner);
+        }
+    }
+
+    #[test]
+    fn parses_key_value_pairs() {
+        assert_eq!(
+            parse_key_val("A=b=c"),
+            Ok(("A".to_string(), "b=c".to_string()))
+        );
+        assert_eq!(parse_key_val("A="), Ok(("A".to_string(), String::new())));
+        assert!(parse_key_val("=b").is_err());
+        assert!(parse_key_val("A").is_err());
+    }
+}
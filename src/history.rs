@@ -0,0 +1,242 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::runner::Outcome;
+use crate::storage::PROFILE_SEPARATOR;
+
+const HISTORY_FILE: &str = "history.toml";
+
+/// When a command last ran and how it ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LastRun {
+    /// Seconds since the Unix epoch
+    pub at: u64,
+    pub exit_code: i32,
+}
+
+impl LastRun {
+    pub fn time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.at)
+    }
+
+    /// How long ago the run was, rounded to the largest unit, e.g. `3h ago`
+    pub fn ago(&self) -> String {
+        let elapsed = SystemTime::now()
+            .duration_since(self.time())
+            .unwrap_or_default()
+            .as_secs();
+
+        match elapsed {
+            0..60 => "just now".to_string(),
+            60..3600 => format!("{}m ago", elapsed / 60),
+            3600..86400 => format!("{}h ago", elapsed / 3600),
+            _ => format!("{}d ago", elapsed / 86400),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Document {
+    #[serde(default)]
+    runs: BTreeMap<String, LastRun>,
+}
+
+/// The last run of every command, kept in `history.toml` in the state
+/// directory (`paths::state_dir`), away from the commands themselves.
+///
+/// Commands are named as in the store, and recorded as `profile:name` for
+/// the storage of a profile and by their bare name for the top-level
+/// storage, so that every storage keeps its own history.
+pub struct History {
+    path: PathBuf,
+    /// Profile whose storage holds the commands named without a prefix,
+    /// `None` for the top-level storage
+    profile: Option<String>,
+    /// Prefix naming the commands of the top-level storage while a profile
+    /// is in use, as with `--all-profiles`
+    top_level_prefix: Option<String>,
+}
+
+impl History {
+    pub fn new(dir: &Path) -> Self {
+        History {
+            path: dir.join(HISTORY_FILE),
+            profile: None,
+            top_level_prefix: None,
+        }
+    }
+
+    /// Name commands as a store on the storage of `profile` does, where
+    /// `top_level_prefix` names the commands of the top-level storage
+    pub fn for_profile(mut self, profile: Option<&str>, top_level_prefix: Option<&str>) -> Self {
+        self.profile = profile.map(str::to_string);
+        self.top_level_prefix = top_level_prefix.map(str::to_string);
+        self
+    }
+
+    /// Last run of every command of the store that ran at least once, by name
+    pub fn load(&self) -> Result<BTreeMap<String, LastRun>> {
+        Ok(self
+            .read()?
+            .runs
+            .into_iter()
+            .filter_map(|(key, last_run)| Some((self.name(&key)?, last_run)))
+            .collect())
+    }
+
+    pub fn last_run(&self, name: &str) -> Result<Option<LastRun>> {
+        Ok(self.read()?.runs.get(&self.key(name)).copied())
+    }
+
+    /// Record that `name` just ran with the given outcome
+    pub fn record(&self, name: &str, outcome: &Outcome) -> Result<()> {
+        let at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+
+        self.update(|runs| {
+            runs.insert(
+                self.key(name),
+                LastRun {
+                    at,
+                    exit_code: outcome.exit_code(),
+                },
+            );
+        })
+    }
+
+    /// Keep the last run of a renamed command
+    pub fn rename(&self, current_name: &str, new_name: &str) -> Result<()> {
+        self.update(|runs| {
+            if let Some(last_run) = runs.remove(&self.key(current_name)) {
+                runs.insert(self.key(new_name), last_run);
+            }
+        })
+    }
+
+    /// Drop the last run of a deleted command
+    pub fn forget(&self, name: &str) -> Result<()> {
+        self.update(|runs| {
+            runs.remove(&self.key(name));
+        })
+    }
+
+    /// Key of a command of the store in the history file
+    fn key(&self, name: &str) -> String {
+        match name.split_once(PROFILE_SEPARATOR) {
+            Some((prefix, local)) if Some(prefix) == self.top_level_prefix.as_deref() => {
+                local.to_string()
+            }
+            Some(_) => name.to_string(),
+            None => match &self.profile {
+                Some(profile) => format!("{}{}{}", profile, PROFILE_SEPARATOR, name),
+                None => name.to_string(),
+            },
+        }
+    }
+
+    /// Name in the store of the command recorded under `key`, `None` when
+    /// the store cannot name it
+    fn name(&self, key: &str) -> Option<String> {
+        match key.split_once(PROFILE_SEPARATOR) {
+            Some((profile, local)) if Some(profile) == self.profile.as_deref() => {
+                Some(local.to_string())
+            }
+            Some(_) => Some(key.to_string()),
+            None => match (&self.profile, &self.top_level_prefix) {
+                (None, _) => Some(key.to_string()),
+                (Some(_), Some(prefix)) => Some(format!("{}{}{}", prefix, PROFILE_SEPARATOR, key)),
+                (Some(_), None) => None,
+            },
+        }
+    }
+
+    /// Apply `f` to the runs and write them back
+    fn update(&self, f: impl FnOnce(&mut BTreeMap<String, LastRun>)) -> Result<()> {
+        let mut document = self.read()?;
+        f(&mut document.runs);
+
+        let content = toml::to_string(&document).context("Failed to serialize history")?;
+
+        // Write to a temp file first, so a failure never leaves a truncated history
+        let dir = self.path.parent().context("Invalid history file path")?;
+        let mut file =
+            tempfile::NamedTempFile::new_in(dir).context("Failed to create temp file")?;
+        std::io::Write::write_all(&mut file, content.as_bytes())
+            .context("Failed to write history")?;
+        file.persist(&self.path)
+            .with_context(|| format!("Failed to write {}", self.path.display()))?;
+
+        Ok(())
+    }
+
+    fn read(&self) -> Result<Document> {
+        if !self.path.exists() {
+            return Ok(Document::default());
+        }
+
+        let content = fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read {}", self.path.display()))?;
+
+        toml::from_str(&content).with_context(|| format!("Failed to parse {}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn success() -> Outcome {
+        Outcome::Exited(std::process::ExitStatus::default())
+    }
+
+    #[test]
+    fn profiles_keep_their_own_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let top_level = History::new(dir.path());
+        let work = History::new(dir.path()).for_profile(Some("work"), None);
+
+        top_level.record("deploy", &success()).unwrap();
+        assert_eq!(work.last_run("deploy").unwrap(), None);
+
+        work.record("deploy", &success()).unwrap();
+        work.rename("deploy", "release").unwrap();
+
+        assert!(top_level.last_run("deploy").unwrap().is_some());
+        assert!(work.last_run("release").unwrap().is_some());
+        assert_eq!(
+            top_level.load().unwrap().keys().collect::<Vec<_>>(),
+            ["deploy", "work:release"]
+        );
+        assert_eq!(work.load().unwrap().keys().collect::<Vec<_>>(), ["release"]);
+    }
+
+    #[test]
+    fn every_profile_names_the_same_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let top_level = History::new(dir.path());
+        let work = History::new(dir.path()).for_profile(Some("work"), None);
+        let all = History::new(dir.path()).for_profile(Some("work"), Some("default"));
+
+        top_level.record("deploy", &success()).unwrap();
+        top_level.record("home:backup", &success()).unwrap();
+        work.record("build", &success()).unwrap();
+
+        assert_eq!(
+            all.load().unwrap().keys().collect::<Vec<_>>(),
+            ["build", "default:deploy", "home:backup"]
+        );
+
+        all.forget("default:deploy").unwrap();
+        all.forget("build").unwrap();
+        assert_eq!(
+            top_level.load().unwrap().keys().collect::<Vec<_>>(),
+            ["home:backup"]
+        );
+    }
+}
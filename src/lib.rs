@@ -0,0 +1,35 @@
+//! zerp as a library: load the configuration, browse the command store and
+//! run stored commands without going through the CLI.
+//!
+//! ```no_run
+//! use zerp::config;
+//! use zerp::runner::{Job, RunOptions};
+//!
+//! let config = config::load_config()?;
+//! let store = config.command_store()?;
+//!
+//! for entry in store.iter() {
+//!     println!("{}", entry.name());
+//! }
+//!
+//! let context = config.run_context()?;
+//! let mut job = Job::prepare(&store, "deploy", &RunOptions::default(), &context)?;
+//! job.resolve_secrets(&context)?;
+//! let outcome = job.run()?;
+//! println!("deploy: {}", outcome);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+pub mod config;
+pub mod env;
+pub mod history;
+pub mod lint;
+pub mod metadata;
+pub mod naming;
+pub mod paths;
+pub mod picker;
+pub mod runner;
+pub mod secret;
+pub mod storage;
+pub mod store;
+pub mod template;
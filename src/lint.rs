@@ -0,0 +1,400 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Interpreters whose scripts shellcheck understands
+const SHELLS: [&str; 5] = ["sh", "bash", "dash", "ksh", "busybox"];
+
+const PLACEHOLDER_START: &str = "{{";
+const PLACEHOLDER_END: &str = "}}";
+/// Kind of the only placeholder zerp resolves, `{{secret:name}}`
+const SECRET_KIND: &str = "secret";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Check a script with shellcheck when installed, or with a minimal built-in
+/// checker for unbalanced quotes and missing shebangs otherwise.
+/// Placeholders are always checked, since shellcheck doesn't know about them.
+pub fn lint(path: &Path) -> Result<Vec<Diagnostic>> {
+    let script = std::fs::read_to_string(path).context("Failed to read command file")?;
+    lint_file(path, &script)
+}
+
+/// Check a script that isn't stored in a file
+pub fn lint_script(script: &str) -> Result<Vec<Diagnostic>> {
+    let mut file = tempfile::Builder::new()
+        .prefix("zerp-lint-")
+        .suffix(".sh")
+        .tempfile()
+        .context("Failed to create temp file")?;
+
+    file.write_all(script.as_bytes())
+        .context("Failed to write to temp file")?;
+
+    lint_file(file.path(), script)
+}
+
+fn lint_file(path: &Path, script: &str) -> Result<Vec<Diagnostic>> {
+    let mut diagnostics = check_placeholders(script);
+
+    match interpreter(script) {
+        Some(interpreter) if !SHELLS.contains(&interpreter.as_str()) => {}
+        _ if is_shellcheck_available() => diagnostics.extend(shellcheck(path, script)?),
+        _ => {
+            diagnostics.extend(check_shebang(script));
+            diagnostics.extend(check_quotes(script));
+        }
+    }
+
+    diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+    Ok(diagnostics)
+}
+
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.severity == Severity::Error)
+}
+
+pub fn print_diagnostics(name: &str, diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        let severity = match diagnostic.severity {
+            Severity::Error => "error".red().bold(),
+            Severity::Warning => "warning".yellow().bold(),
+        };
+
+        println!(
+            "{}:{}: {}: {}",
+            name.green(),
+            diagnostic.line,
+            severity,
+            diagnostic.message
+        );
+    }
+}
+
+pub fn is_shellcheck_available() -> bool {
+    Command::new("shellcheck")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+/// Name of the interpreter from the script's shebang, if it has one
+fn interpreter(script: &str) -> Option<String> {
+    let shebang = script.lines().next()?.strip_prefix("#!")?;
+    let mut parts = shebang.split_whitespace();
+    let program = parts.next()?.rsplit('/').next()?;
+
+    let program = if program == "env" {
+        parts.find(|part| !part.starts_with('-'))?
+    } else {
+        program
+    };
+
+    Some(program.to_string())
+}
+
+fn shellcheck(path: &Path, script: &str) -> Result<Vec<Diagnostic>> {
+    let mut command = Command::new("shellcheck");
+    command.arg("--format=gcc");
+
+    // Scripts without a shebang are run with `sh`
+    if interpreter(script).is_none() {
+        command.arg("--shell=sh");
+    }
+
+    let output = command
+        .arg(path)
+        .output()
+        .context("Failed to run shellcheck")?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    // Lines look like `file:line:column: severity: message [SCxxxx]`
+    let diagnostics = stdout
+        .lines()
+        .filter_map(|line| {
+            let rest = line.strip_prefix(path.to_str()?)?.strip_prefix(':')?;
+            let mut parts = rest.splitn(4, ':');
+            let line = parts.next()?.trim().parse().ok()?;
+            let _column = parts.next()?;
+            let severity = match parts.next()?.trim() {
+                "error" => Severity::Error,
+                _ => Severity::Warning,
+            };
+            let message = parts.next()?.trim().to_string();
+
+            Some(Diagnostic {
+                line,
+                severity,
+                message,
+            })
+        })
+        .collect();
+
+    Ok(diagnostics)
+}
+
+fn check_shebang(script: &str) -> Option<Diagnostic> {
+    if script.starts_with("#!") {
+        return None;
+    }
+
+    Some(Diagnostic {
+        line: 1,
+        severity: Severity::Warning,
+        message: "missing shebang (the script will be run with sh)".to_string(),
+    })
+}
+
+/// Report quotes that are never closed, ignoring escaped quotes, comments
+/// and here-document bodies
+fn check_quotes(script: &str) -> Vec<Diagnostic> {
+    let mut open: Option<(char, usize)> = None;
+    let mut escaped = false;
+    let mut lines = script.lines().enumerate();
+
+    while let Some((index, line)) = lines.next() {
+        let mut previous = ' ';
+        // Delimiters of the here-documents starting on this line, whose
+        // bodies follow it in order
+        let mut heredocs = Vec::new();
+        let mut skip_to = 0;
+
+        for (position, c) in line.char_indices() {
+            if position < skip_to {
+                continue;
+            }
+
+            if escaped {
+                escaped = false;
+                previous = c;
+                continue;
+            }
+
+            match (open, c) {
+                (Some(('\'', _)), '\'') => open = None,
+                (Some(('\'', _)), _) => {}
+                (_, '\\') => escaped = true,
+                (Some((quote, _)), c) if c == quote => open = None,
+                (Some(_), _) => {}
+                (None, '#') if previous.is_whitespace() => break,
+                (None, '<') if previous != '<' => {
+                    if let Some((delimiter, length)) = heredoc_delimiter(&line[position..]) {
+                        heredocs.push(delimiter);
+                        skip_to = position + length;
+                    }
+                }
+                (None, '\'' | '"' | '`') => open = Some((c, index + 1)),
+                (None, _) => {}
+            }
+
+            previous = c;
+        }
+
+        // A trailing backslash continues the line; it doesn't escape the newline's successor
+        escaped = false;
+
+        for delimiter in heredocs {
+            for (_, body) in lines.by_ref() {
+                // `<<-` strips leading tabs from the closing line
+                if body.trim_start_matches('\t') == delimiter {
+                    break;
+                }
+            }
+        }
+    }
+
+    match open {
+        Some((quote, line)) => vec![Diagnostic {
+            line,
+            severity: Severity::Error,
+            message: format!("unbalanced {} quote", quote),
+        }],
+        None => Vec::new(),
+    }
+}
+
+/// Delimiter of the here-document that `text` starts, as in `<<EOF`,
+/// `<<-EOF` or `<< 'EOF'`, along with the length of its redirection.
+/// Here-strings (`<<<`) start none.
+fn heredoc_delimiter(text: &str) -> Option<(String, usize)> {
+    let rest = text.strip_prefix("<<")?;
+
+    if rest.starts_with('<') {
+        return None;
+    }
+
+    let word = rest.strip_prefix('-').unwrap_or(rest).trim_start();
+    let offset = text.len() - word.len();
+
+    let (delimiter, length) = match word.chars().next()? {
+        quote @ ('\'' | '"') => {
+            let end = word[1..].find(quote)? + 1;
+            (word[1..end].to_string(), end + 1)
+        }
+        _ => {
+            let length = word
+                .find(|c: char| c.is_whitespace() || ";&|<>()".contains(c))
+                .unwrap_or(word.len());
+            (word[..length].replace('\\', ""), length)
+        }
+    };
+
+    if delimiter.is_empty() {
+        return None;
+    }
+
+    Some((delimiter, offset + length))
+}
+
+/// Report `{{kind:name}}` placeholders that zerp doesn't know how to resolve.
+///
+/// Other uses of braces, such as Go templates (`{{.Names}}`), are left alone.
+fn check_placeholders(script: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    for (index, line) in script.lines().enumerate() {
+        let mut rest = line;
+
+        while let Some(start) = rest.find(PLACEHOLDER_START) {
+            rest = &rest[start + PLACEHOLDER_START.len()..];
+
+            let Some(end) = rest.find(PLACEHOLDER_END) else {
+                if rest.starts_with(&format!("{}:", SECRET_KIND)) {
+                    diagnostics.push(Diagnostic {
+                        line: index + 1,
+                        severity: Severity::Error,
+                        message: "unterminated placeholder, missing '}}'".to_string(),
+                    });
+                }
+                break;
+            };
+
+            let placeholder = &rest[..end];
+            rest = &rest[end + PLACEHOLDER_END.len()..];
+
+            let Some((kind, name)) = placeholder.split_once(':') else {
+                continue;
+            };
+
+            if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_lowercase()) {
+                continue;
+            }
+
+            if kind != SECRET_KIND || name.is_empty() {
+                diagnostics.push(Diagnostic {
+                    line: index + 1,
+                    severity: Severity::Error,
+                    message: format!(
+                        "undefined placeholder '{}{}{}'",
+                        PLACEHOLDER_START, placeholder, PLACEHOLDER_END
+                    ),
+                });
+            }
+        }
+    }
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(diagnostics: &[Diagnostic]) -> Vec<usize> {
+        diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.line)
+            .collect()
+    }
+
+    #[test]
+    fn balanced_quotes() {
+        let script = "echo 'single' \"double\" `date`\necho \"it's\" \\' # don't\n";
+        assert!(check_quotes(script).is_empty());
+    }
+
+    #[test]
+    fn unbalanced_quote_reports_its_line() {
+        let diagnostics = check_quotes("echo ok\necho \"open\necho more\n");
+        assert_eq!(lines(&diagnostics), [2]);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].message, "unbalanced \" quote");
+    }
+
+    #[test]
+    fn quotes_spanning_lines() {
+        assert!(check_quotes("echo 'first\nsecond'\n").is_empty());
+    }
+
+    #[test]
+    fn heredoc_bodies_are_skipped() {
+        let script = "cat <<EOF\nit's fine\nEOF\ncat <<- 'END' > file\n\tdon't\n\tEND\necho done\n";
+        assert!(check_quotes(script).is_empty());
+    }
+
+    #[test]
+    fn several_heredocs_on_one_line() {
+        let script = "paste <<A <<\"B\"\nit's\nA\nwon't\nB\necho 'open\n";
+        assert_eq!(lines(&check_quotes(script)), [6]);
+    }
+
+    #[test]
+    fn here_strings_and_quoted_markers_are_not_heredocs() {
+        assert!(check_quotes("cat <<< 'text'\necho \"<<EOF\"\necho 'x'\n").is_empty());
+        assert_eq!(lines(&check_quotes("cat <<< it's\n")), [1]);
+    }
+
+    #[test]
+    fn secret_placeholders_are_valid() {
+        assert!(check_placeholders("curl -H {{secret:token}} {{secret:a.b-c}}\n").is_empty());
+    }
+
+    #[test]
+    fn unknown_placeholders_are_errors() {
+        let diagnostics = check_placeholders("echo ok\necho {{env:HOME}} {{secret:}}\n");
+        assert_eq!(lines(&diagnostics), [2, 2]);
+        assert_eq!(
+            diagnostics[0].message,
+            "undefined placeholder '{{env:HOME}}'"
+        );
+        assert_eq!(
+            diagnostics[1].message,
+            "undefined placeholder '{{secret:}}'"
+        );
+    }
+
+    #[test]
+    fn unterminated_secret_placeholder() {
+        let diagnostics = check_placeholders("echo {{secret:token\n");
+        assert_eq!(lines(&diagnostics), [1]);
+        assert_eq!(
+            diagnostics[0].message,
+            "unterminated placeholder, missing '}}'"
+        );
+    }
+
+    #[test]
+    fn other_braces_are_ignored() {
+        let script = "docker ps --format '{{.Names}}'\necho {{ a:b }} {{Kind:x}} {{\n";
+        assert!(check_placeholders(script).is_empty());
+    }
+}
@@ -1,40 +1,169 @@
 mod cli;
 mod completion;
-mod config;
-mod fzf;
+mod draft;
 mod state;
+mod ui;
 
 use anyhow::Ok;
 use clap::Parser;
-use cli::{Cli, Commands};
+use cli::{Cli, Commands, ConfigAction, SecretAction, TagAction};
+use colored::Colorize;
 use state::State;
+use zerp::config;
+use zerp::paths;
+use zerp::runner::{CommandFailed, RunOptions};
+use zerp::storage;
 
-fn main() -> anyhow::Result<()> {
+fn main() {
     let cli = Cli::parse();
-    let config = config::load_config()?;
+    let quiet_errors = matches!(
+        cli.command,
+        Some(Commands::Run {
+            quiet_errors: true,
+            ..
+        })
+    );
 
-    let state = State::new(config.storage.clone(), config.editor.clone());
+    if let Err(err) = run(cli) {
+        // A failing stored command makes zerp exit with that command's status
+        if let Some(failed) = err.downcast_ref::<CommandFailed>() {
+            if !quiet_errors {
+                eprintln!("{}", failed.to_string().red());
+            }
+            std::process::exit(failed.code);
+        }
+
+        eprintln!("{}", format!("Error: {:?}", err).red());
+        std::process::exit(1);
+    }
+}
+
+/// Load the configuration, first moving the files of the legacy `~/.zerp`
+/// directory and creating the user config file on first run
+fn load_config(overrides: &config::ConfigOverrides) -> anyhow::Result<config::CliConfig> {
+    let moves = paths::migrate_legacy()?;
+
+    if !moves.is_empty() {
+        eprintln!(
+            "{}",
+            "zerp now follows the XDG base directories, moved from ~/.zerp:".yellow()
+        );
+        for (from, to) in moves {
+            eprintln!("  {} -> {}", from.display(), to.display());
+        }
+    }
+
+    if let Some(path) = config::create_user_config()? {
+        eprintln!("Created default config at: {}", path.display());
+    }
+
+    let config = config::load_config_with(overrides)?;
+
+    for warning in &config.warnings {
+        eprintln!("{}", format!("Warning: {}", warning).yellow());
+    }
+
+    Ok(config)
+}
+
+fn run(cli: Cli) -> anyhow::Result<()> {
+    let overrides = config::ConfigOverrides {
+        config_file: cli.config.clone(),
+        storage: cli.storage_dir.clone(),
+        profile: cli.profile.clone(),
+        all_profiles: cli.all_profiles,
+    };
+
+    // An invalid config must not keep `zerp config` from opening it for a fix
+    if let Some(Commands::Config {
+        action: None,
+        editor: None,
+        storage: None,
+    }) = &cli.command
+    {
+        let config = load_config(&overrides).unwrap_or_else(|err| {
+            eprintln!("{}", format!("{:#}", err).red());
+            config::CliConfig::default()
+        });
+        return config.edit();
+    }
+
+    let config = load_config(&overrides)?;
+
+    let mut state = State::new(config.command_store()?, config.editor.clone())
+        .with_run_context(config.run_context()?)
+        .with_lint_on_save(config.lint_on_save)
+        .with_default_template(config.default_template.clone())
+        .with_picker(config.picker(cli.picker)?);
 
     match cli.command {
-        Some(Commands::Add { name }) => state.add(name),
+        Some(Commands::Add { name, template }) => state.add(name, template),
 
         Some(Commands::List) => {
             state.list()?;
             Ok(())
         }
 
-        Some(Commands::Run { name }) => {
-            state.run(name)?;
+        Some(Commands::Run {
+            names,
+            parallel,
+            jobs,
+            cwd,
+            env,
+            dry_run,
+            timeout,
+            retries,
+            retry_delay,
+            backoff,
+            quiet_errors: _,
+            yes,
+        }) => {
+            let options = RunOptions {
+                cwd,
+                env,
+                dry_run,
+                timeout,
+                retries,
+                retry_delay,
+                backoff,
+                yes,
+            };
+
+            if parallel {
+                state.run_parallel(names, jobs, &options)?;
+            } else if names.len() > 1 {
+                anyhow::bail!("Use --parallel to run several commands at once");
+            } else {
+                state.run(names.into_iter().next(), &options)?;
+            }
+            Ok(())
+        }
+
+        Some(Commands::Delete { names }) => {
+            state.delete(names)?;
+            Ok(())
+        }
+
+        Some(Commands::Tag { action }) => {
+            match action {
+                TagAction::Add { tag, names } => state.tag(tag, names, false)?,
+                TagAction::Rm { tag, names } => state.tag(tag, names, true)?,
+            }
+            Ok(())
+        }
+
+        Some(Commands::Show { name }) => {
+            state.show(name)?;
             Ok(())
         }
 
-        Some(Commands::Delete { name }) => {
-            state.delete(name)?;
+        Some(Commands::Edit { name, meta }) => {
+            state.edit(name, meta)?;
             Ok(())
         }
 
-        Some(Commands::Edit { name }) => {
-            state.edit(name)?;
+        Some(Commands::Lint { name, all }) => {
+            state.lint(name, all)?;
             Ok(())
         }
 
@@ -46,11 +175,150 @@ fn main() -> anyhow::Result<()> {
             Ok(())
         }
 
-        Some(Commands::Config { editor, storage }) => {
-            if None == editor && None == storage {
-                config.edit()?;
+        Some(Commands::Cp {
+            source,
+            destination,
+            edit,
+        }) => {
+            state.copy(source, destination, edit)?;
+            Ok(())
+        }
+
+        Some(Commands::Ui) => ui::run(&mut state),
+
+        Some(Commands::Migrate {
+            to,
+            from,
+            remove_source,
+        }) => {
+            if cli.all_profiles {
+                anyhow::bail!("Migrate one storage at a time, without --all-profiles");
             }
 
+            let from = from.unwrap_or(config.storage_backend);
+
+            if from == to {
+                anyhow::bail!("Commands are already stored with the {} backend", to);
+            }
+
+            let mut source = from.open(&config.storage)?;
+            let mut target = to.open(&config.storage)?;
+            let count = storage::migrate(source.as_ref(), target.as_mut())?;
+
+            config::set_storage_backend(to, config.active_profile())?;
+            println!(
+                "Migrated {} commands from {} to {}",
+                count,
+                source.location(),
+                target.location()
+            );
+
+            if remove_source {
+                storage::remove_all(source.as_mut())?;
+                println!("Removed the commands from {}.", source.location());
+            } else {
+                println!("The {} data was left in place.", from);
+            }
+            Ok(())
+        }
+
+        Some(Commands::Config {
+            action: Some(ConfigAction::Show { origin }),
+            ..
+        }) => {
+            if !origin {
+                print!("{}", toml::to_string(&config.to_table()?)?);
+                return Ok(());
+            }
+
+            let values = config::effective_values(&config, &overrides)?;
+            let width = values
+                .iter()
+                .map(|(key, value, _)| key.len() + value.len())
+                .max()
+                .unwrap_or_default();
+
+            for (key, value, origin) in values {
+                let setting = format!("{} = {}", key, value);
+                println!(
+                    "{:<width$}  {}",
+                    setting,
+                    format!("# {}", origin).dimmed(),
+                    width = width + 3
+                );
+            }
+
+            Ok(())
+        }
+
+        Some(Commands::Config {
+            action: Some(ConfigAction::Get { key }),
+            ..
+        }) => match config::get_setting(&config, &key)? {
+            Some(toml::Value::String(value)) => {
+                println!("{}", value);
+                Ok(())
+            }
+            Some(toml::Value::Table(table)) => {
+                print!("{}", toml::to_string(&table)?);
+                Ok(())
+            }
+            Some(value) => {
+                println!("{}", value);
+                Ok(())
+            }
+            None => anyhow::bail!("{}", format!("{} is not set", key).red()),
+        },
+
+        Some(Commands::Config {
+            action: Some(ConfigAction::Set { key, value }),
+            ..
+        }) => {
+            let value = config::set_setting(&key, &value)?;
+            println!("{} set to: {}", key, value);
+            Ok(())
+        }
+
+        Some(Commands::Config {
+            action: Some(ConfigAction::Unset { key }),
+            ..
+        }) => {
+            if config::unset_setting(&key)? {
+                println!("{} unset", key);
+            } else {
+                println!("{} is not set in the user config file", key);
+            }
+            Ok(())
+        }
+
+        Some(Commands::Config {
+            action: Some(ConfigAction::List),
+            ..
+        }) => {
+            let values = config::effective_values(&config, &overrides)?;
+
+            for (key, value, _) in &values {
+                println!("{} = {}", key, value);
+            }
+
+            for (key, _) in config::SETTINGS {
+                let is_set = values
+                    .iter()
+                    .any(|(name, _, _)| name == key || name.starts_with(&format!("{}.", key)));
+
+                if !is_set {
+                    println!("{}", format!("{} (not set)", key).dimmed());
+                }
+            }
+
+            Ok(())
+        }
+
+        Some(Commands::Config {
+            action: None,
+            editor,
+            storage,
+        }) => {
             if let Some(editor) = editor {
                 config::set_editor(&editor)?;
                 println!("Editor set to: {}", editor);
@@ -64,13 +332,42 @@ fn main() -> anyhow::Result<()> {
             Ok(())
         }
 
+        Some(Commands::Secret { action }) => {
+            let secrets = config.secret_store()?;
+
+            match action {
+                SecretAction::Set { name, value } => {
+                    let value = match value {
+                        Some(value) => value,
+                        None => dialoguer::Password::new()
+                            .with_prompt(format!("Value for {}", name))
+                            .interact()?,
+                    };
+                    secrets.set(&name, value)?;
+                }
+                SecretAction::Get { name } => println!("{}", secrets.get(&name)?),
+                SecretAction::List => {
+                    for name in secrets.load()?.keys() {
+                        println!("{}", name);
+                    }
+                }
+                SecretAction::Rm { name } => secrets.remove(&name)?,
+                SecretAction::Keygen => {
+                    let path = secrets.keygen()?;
+                    println!("Key file created at: {}", path.display());
+                }
+            }
+
+            Ok(())
+        }
+
         Some(Commands::Completion { shell }) => {
             completion::generate_completion(shell)?;
             Ok(())
         }
 
         None => {
-            Cli::parse_from(["zerp", "--help"]);
+            state.browse()?;
             Ok(())
         }
     }
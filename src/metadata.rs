@@ -0,0 +1,148 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Suffix of the file holding a command's metadata, next to its `.sh` file
+pub const METADATA_SUFFIX: &str = ".meta.toml";
+
+/// Value of `cwd` that resolves to the root of the current project
+pub const PROJECT_ROOT: &str = "@project";
+
+/// Markers identifying the root directory of a project
+const PROJECT_MARKERS: [&str; 2] = [".zerp", ".git"];
+
+/// Optional settings stored alongside a command
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    /// Short summary of what the command does
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Free-form labels used to group commands
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// Directory the command runs in (`~` is expanded, `@project` is the project root)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+
+    /// Dotenv file loaded before running (relative paths start from `cwd`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_file: Option<String>,
+
+    /// Default timeout, e.g. `"30s"` or `"5m"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<String>,
+
+    /// Default number of retries after a failed run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+
+    /// Default delay before retrying, e.g. `"5s"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_delay: Option<String>,
+
+    /// Double the retry delay after every failed attempt
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub backoff: bool,
+
+    /// Require typing the command name before it runs
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub confirm: bool,
+
+    /// Environment variables set for the command
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+}
+
+impl Metadata {
+    /// Load metadata from `path`, falling back to defaults when the file is missing
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Metadata::default());
+        }
+
+        let content = fs::read_to_string(path).context("Failed to read metadata file")?;
+        toml::from_str(&content)
+            .with_context(|| format!("Failed to parse metadata file {}", path.display()))
+    }
+
+    /// Write metadata to `path`, removing the file when nothing is set
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if *self == Metadata::default() {
+            if path.exists() {
+                fs::remove_file(path).context("Failed to delete metadata file")?;
+            }
+            return Ok(());
+        }
+
+        let content = toml::to_string(self).context("Failed to serialize metadata")?;
+        fs::write(path, content).context("Failed to write metadata file")?;
+        Ok(())
+    }
+
+    pub fn timeout(&self) -> Result<Option<Duration>> {
+        parse_duration("timeout", self.timeout.as_deref())
+    }
+
+    pub fn retry_delay(&self) -> Result<Option<Duration>> {
+        parse_duration("retry_delay", self.retry_delay.as_deref())
+    }
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+fn parse_duration(key: &str, value: Option<&str>) -> Result<Option<Duration>> {
+    value
+        .map(|value| {
+            humantime::parse_duration(value)
+                .with_context(|| format!("Invalid {} in metadata: '{}'", key, value))
+        })
+        .transpose()
+}
+
+/// Resolve the directory a command should run in.
+///
+/// An explicit `cwd_override` wins over the `cwd` stored in metadata. `None`
+/// means the command runs in the current directory.
+pub fn resolve_cwd(cwd_override: Option<&str>, metadata: &Metadata) -> Result<Option<PathBuf>> {
+    let Some(cwd) = cwd_override.or(metadata.cwd.as_deref()) else {
+        return Ok(None);
+    };
+
+    let dir = if cwd == PROJECT_ROOT {
+        find_project_root().context("Not inside a project (no .zerp or .git directory found)")?
+    } else {
+        PathBuf::from(shellexpand::tilde(cwd).into_owned())
+    };
+
+    if !dir.is_dir() {
+        anyhow::bail!("Working directory does not exist: {}", dir.display());
+    }
+
+    Ok(Some(dir))
+}
+
+/// Find the nearest ancestor of the current directory that looks like a project root.
+///
+/// The home directory is never considered a project, since it may hold the
+/// legacy global `~/.zerp` directory.
+pub fn find_project_root() -> Option<PathBuf> {
+    let current = std::env::current_dir().ok()?;
+    let home = dirs::home_dir();
+
+    current
+        .ancestors()
+        .take_while(|dir| Some(*dir) != home.as_deref())
+        .find(|dir| {
+            PROJECT_MARKERS
+                .iter()
+                .any(|marker| dir.join(marker).is_dir())
+        })
+        .map(Path::to_path_buf)
+}
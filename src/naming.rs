@@ -0,0 +1,144 @@
+use anyhow::Result;
+use std::path::Path;
+
+/// Longest name accepted for a command, leaving room for the file extensions
+const MAX_LENGTH: usize = 64;
+
+/// Names Windows refuses as file names, whatever the extension
+const RESERVED: [&str; 22] = [
+    "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8",
+    "com9", "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+/// Check a name given to a new command: letters, digits, `_`, `-` and `.`,
+/// not starting with `.` or `-`, and not a reserved file name.
+pub fn validate(name: &str) -> Result<()> {
+    check_path_safe(name)?;
+
+    if name.len() > MAX_LENGTH {
+        anyhow::bail!(
+            "Invalid command name '{}': longer than {} characters",
+            name,
+            MAX_LENGTH
+        );
+    }
+
+    if let Some(c) = name
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')))
+    {
+        anyhow::bail!(
+            "Invalid command name '{}': '{}' is not allowed, use letters, digits, '_', '-' or '.'",
+            name,
+            c
+        );
+    }
+
+    if name.starts_with(['.', '-']) {
+        anyhow::bail!(
+            "Invalid command name '{}': cannot start with '.' or '-'",
+            name
+        );
+    }
+
+    let stem = name.split('.').next().unwrap_or_default();
+    if RESERVED.contains(&stem.to_ascii_lowercase().as_str()) {
+        anyhow::bail!("Invalid command name '{}': the name is reserved", name);
+    }
+
+    Ok(())
+}
+
+/// Check a name used to look up a command, so that it can't point outside
+/// the storage directory. Existing commands with unusual names still work.
+pub fn check_path_safe(name: &str) -> Result<()> {
+    if name.trim().is_empty() {
+        anyhow::bail!("Command name cannot be empty");
+    }
+
+    if name.contains(['/', '\\', '\0']) || name == "." || name == ".." {
+        anyhow::bail!(
+            "Invalid command name '{}': names cannot contain path separators",
+            name
+        );
+    }
+
+    Ok(())
+}
+
+/// Whether files in `dir` can be reached regardless of case
+pub fn is_case_insensitive(dir: &Path) -> bool {
+    let Ok(file) = tempfile::Builder::new()
+        .prefix(".zerp-case-check-")
+        .tempfile_in(dir)
+    else {
+        return false;
+    };
+
+    let Some(file_name) = file.path().file_name().and_then(|s| s.to_str()) else {
+        return false;
+    };
+
+    dir.join(file_name.to_ascii_uppercase()).exists()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_plain_names() {
+        for name in ["deploy", "db.backup", "build-all_2", "a", "Deploy.Prod"] {
+            assert!(validate(name).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn rejects_paths() {
+        for name in ["../x", "a/b", "a\\b", ".", "..", "a\0b", "", "  "] {
+            assert!(check_path_safe(name).is_err(), "{:?}", name);
+            assert!(validate(name).is_err(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn lookups_allow_unusual_names() {
+        for name in [".hidden", "-flag", "con", "with space"] {
+            assert!(check_path_safe(name).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn rejects_leading_dot_or_dash() {
+        for name in [".hidden", "-flag", "..x"] {
+            let err = validate(name).unwrap_err();
+            assert!(err.to_string().contains("cannot start with"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn rejects_other_characters() {
+        for name in ["with space", "semi;colon", "dollar$", "é"] {
+            assert!(validate(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn rejects_reserved_names() {
+        for name in ["con", "NUL", "nul.txt", "Com1.sh", "lpt9"] {
+            let err = validate(name).unwrap_err();
+            assert!(err.to_string().contains("reserved"), "{}", err);
+        }
+
+        assert!(validate("console").is_ok());
+        assert!(validate("com10").is_ok());
+    }
+
+    #[test]
+    fn length_limit() {
+        assert!(validate(&"a".repeat(MAX_LENGTH)).is_ok());
+
+        let err = validate(&"a".repeat(MAX_LENGTH + 1)).unwrap_err();
+        assert!(err.to_string().contains("longer than"), "{}", err);
+    }
+}
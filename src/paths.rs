@@ -0,0 +1,351 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of zerp's directory under each XDG base directory
+const APP_DIR: &str = "zerp";
+/// Directory in the home directory that held everything before zerp used
+/// the XDG directories
+const LEGACY_DIR: &str = ".zerp";
+const CONFIG_FILE_NAME: &str = "config.toml";
+const HISTORY_FILE_NAME: &str = "history.toml";
+const SECRETS_FILES: [&str; 2] = ["secrets.age", "secrets.key"];
+
+/// Settings: `$XDG_CONFIG_HOME/zerp`, `~/.config/zerp` by default
+pub fn config_dir() -> Result<PathBuf> {
+    app_dir("XDG_CONFIG_HOME", ".config")
+}
+
+/// Commands and secrets: `$XDG_DATA_HOME/zerp`, `~/.local/share/zerp` by
+/// default
+pub fn data_dir() -> Result<PathBuf> {
+    app_dir("XDG_DATA_HOME", ".local/share")
+}
+
+/// Run history: `$XDG_STATE_HOME/zerp`, `~/.local/state/zerp` by default
+pub fn state_dir() -> Result<PathBuf> {
+    app_dir("XDG_STATE_HOME", ".local/state")
+}
+
+/// Files that can be rebuilt at any time: `$XDG_CACHE_HOME/zerp`,
+/// `~/.cache/zerp` by default
+pub fn cache_dir() -> Result<PathBuf> {
+    app_dir("XDG_CACHE_HOME", ".cache")
+}
+
+/// zerp's directory under an XDG base directory, created if needed. As the
+/// spec requires, relative paths in the variable are ignored.
+fn app_dir(var: &str, default: &str) -> Result<PathBuf> {
+    let base = match std::env::var_os(var).map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => dir,
+        _ => home_dir()?.join(default),
+    };
+    let dir = base.join(APP_DIR);
+
+    if !dir.exists() {
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+
+    Ok(dir)
+}
+
+fn home_dir() -> Result<PathBuf> {
+    dirs::home_dir().context("Failed to determine home directory")
+}
+
+/// Move the files of the legacy `~/.zerp` directory to the XDG directories:
+/// the config file to the config directory, the history to the state
+/// directory, and secrets and commands to the data directory. Commands only
+/// move when they were kept in `~/.zerp`, in which case the `storage`
+/// setting pointing there is dropped.
+///
+/// Runs while `~/.zerp/config.toml` exists and the new config file does
+/// not. Nothing moves when any destination is taken, and the config file
+/// moves last, so an interrupted migration is picked up by the next run.
+/// Returns every move made.
+pub fn migrate_legacy() -> Result<Vec<(PathBuf, PathBuf)>> {
+    let legacy = home_dir()?.join(LEGACY_DIR);
+    let config_dir = config_dir()?;
+
+    if !legacy.join(CONFIG_FILE_NAME).is_file() || config_dir.join(CONFIG_FILE_NAME).exists() {
+        return Ok(Vec::new());
+    }
+
+    move_legacy(&legacy, &config_dir, &data_dir()?, &state_dir()?)
+}
+
+/// Move the files of `legacy` to the given directories, as described in
+/// `migrate_legacy`
+fn move_legacy(
+    legacy: &Path,
+    config_dir: &Path,
+    data: &Path,
+    state: &Path,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let legacy_config = legacy.join(CONFIG_FILE_NAME);
+    let config = config_dir.join(CONFIG_FILE_NAME);
+
+    let content = fs::read_to_string(&legacy_config)
+        .with_context(|| format!("Failed to read {}", legacy_config.display()))?;
+    let table = content
+        .parse::<toml::Table>()
+        .with_context(|| format!("Failed to parse {}", legacy_config.display()))?;
+
+    let commands_in_legacy = match table.get("storage").and_then(|storage| storage.as_str()) {
+        Some(storage) => same_dir(Path::new(&*shellexpand::tilde(storage)), legacy),
+        None => true,
+    };
+
+    let mut planned = Vec::new();
+
+    let history = legacy.join(HISTORY_FILE_NAME);
+    if history.exists() {
+        planned.push((history, state.join(HISTORY_FILE_NAME)));
+    }
+
+    for name in SECRETS_FILES {
+        let file = legacy.join(name);
+        if file.exists() {
+            planned.push((file, data.join(name)));
+        }
+    }
+
+    if commands_in_legacy {
+        let entries =
+            fs::read_dir(legacy).with_context(|| format!("Failed to read {}", legacy.display()))?;
+
+        for entry in entries {
+            let entry = entry.context("Failed to read directory entry")?;
+            let path = entry.path();
+
+            if path != legacy_config && !planned.iter().any(|(from, _)| *from == path) {
+                planned.push((path, data.join(entry.file_name())));
+            }
+        }
+    }
+
+    if let Some((from, to)) = planned.iter().find(|(_, to)| to.exists()) {
+        anyhow::bail!(
+            "Cannot move {} to {}: it already exists",
+            from.display(),
+            to.display()
+        );
+    }
+
+    let mut moves = Vec::new();
+
+    for (from, to) in planned {
+        moves.push(move_path(&from, &to)?);
+    }
+
+    // Written as is, so comments and formatting survive
+    let content = if commands_in_legacy {
+        remove_top_level_key(&content, "storage")
+    } else {
+        content
+    };
+
+    fs::write(&config, content).with_context(|| format!("Failed to write {}", config.display()))?;
+    fs::remove_file(&legacy_config)
+        .with_context(|| format!("Failed to remove {}", legacy_config.display()))?;
+    moves.push((legacy_config, config));
+
+    // Only succeeds once everything moved out
+    let _ = fs::remove_dir(legacy);
+
+    Ok(moves)
+}
+
+/// Drop the line setting `key` before the first table of a TOML document,
+/// leaving every other line untouched
+fn remove_top_level_key(content: &str, key: &str) -> String {
+    let mut top_level = true;
+
+    content
+        .split_inclusive('\n')
+        .filter(|line| {
+            let line = line.trim();
+            top_level &= !line.starts_with('[');
+
+            let sets_key = line
+                .strip_prefix(key)
+                .is_some_and(|rest| rest.trim_start().starts_with('='));
+
+            !(top_level && sets_key)
+        })
+        .collect()
+}
+
+fn same_dir(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Move a file or directory, copying it when it cannot be renamed, e.g.
+/// across file systems
+fn move_path(from: &Path, to: &Path) -> Result<(PathBuf, PathBuf)> {
+    if to.exists() {
+        anyhow::bail!(
+            "Cannot move {} to {}: it already exists",
+            from.display(),
+            to.display()
+        );
+    }
+
+    if fs::rename(from, to).is_err() {
+        copy_recursive(from, to)
+            .with_context(|| format!("Failed to copy {} to {}", from.display(), to.display()))?;
+
+        if from.is_dir() {
+            fs::remove_dir_all(from)
+        } else {
+            fs::remove_file(from)
+        }
+        .with_context(|| format!("Failed to remove {}", from.display()))?;
+    }
+
+    Ok((from.to_path_buf(), to.to_path_buf()))
+}
+
+fn copy_recursive(from: &Path, to: &Path) -> std::io::Result<()> {
+    if !from.is_dir() {
+        return fs::copy(from, to).map(|_| ());
+    }
+
+    fs::create_dir_all(to)?;
+
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_only_the_top_level_key() {
+        let content = "\
+# Where commands live
+storage = \"~/.zerp\"
+storage_backend = \"toml\"
+editor = \"vim\" # inline comment
+
+[profiles.work]
+storage = \"~/work\"
+";
+
+        assert_eq!(
+            remove_top_level_key(content, "storage"),
+            "\
+# Where commands live
+storage_backend = \"toml\"
+editor = \"vim\" # inline comment
+
+[profiles.work]
+storage = \"~/work\"
+"
+        );
+    }
+
+    #[test]
+    fn removes_keys_without_spaces_and_keeps_commented_ones() {
+        let content = "# storage = \"/old\"\nstorage=\"/x\"\n  storage   = \"/y\"\n";
+        assert_eq!(
+            remove_top_level_key(content, "storage"),
+            "# storage = \"/old\"\n"
+        );
+    }
+
+    struct Dirs {
+        _home: tempfile::TempDir,
+        legacy: PathBuf,
+        config: PathBuf,
+        data: PathBuf,
+        state: PathBuf,
+    }
+
+    impl Dirs {
+        fn new(config: &str) -> Dirs {
+            let home = tempfile::tempdir().unwrap();
+            let dir = |name: &str| {
+                let dir = home.path().join(name);
+                fs::create_dir_all(&dir).unwrap();
+                dir
+            };
+
+            let dirs = Dirs {
+                legacy: dir(LEGACY_DIR),
+                config: dir("config"),
+                data: dir("data"),
+                state: dir("state"),
+                _home: home,
+            };
+
+            fs::write(dirs.legacy.join(CONFIG_FILE_NAME), config).unwrap();
+            fs::write(dirs.legacy.join(HISTORY_FILE_NAME), "").unwrap();
+            fs::write(dirs.legacy.join("secrets.age"), "").unwrap();
+            fs::write(dirs.legacy.join("deploy.sh"), "echo deploy").unwrap();
+            dirs
+        }
+
+        fn migrate(&self) -> Result<Vec<(PathBuf, PathBuf)>> {
+            move_legacy(&self.legacy, &self.config, &self.data, &self.state)
+        }
+    }
+
+    #[test]
+    fn moves_everything_out_of_the_legacy_directory() {
+        let dirs = Dirs::new("");
+        let config = format!(
+            "# My settings\nstorage = \"{}\"\nstorage_backend = \"fs\"\n\n[profiles.work]\nstorage = \"/work\"\n",
+            dirs.legacy.display()
+        );
+        fs::write(dirs.legacy.join(CONFIG_FILE_NAME), config).unwrap();
+
+        assert_eq!(dirs.migrate().unwrap().len(), 4);
+
+        assert!(dirs.state.join(HISTORY_FILE_NAME).exists());
+        assert!(dirs.data.join("secrets.age").exists());
+        assert!(dirs.data.join("deploy.sh").exists());
+        assert!(!dirs.legacy.exists());
+        assert_eq!(
+            fs::read_to_string(dirs.config.join(CONFIG_FILE_NAME)).unwrap(),
+            "# My settings\nstorage_backend = \"fs\"\n\n[profiles.work]\nstorage = \"/work\"\n"
+        );
+    }
+
+    #[test]
+    fn commands_stored_elsewhere_stay() {
+        let config = "# Kept as is\nstorage = \"/elsewhere\"\n";
+        let dirs = Dirs::new(config);
+
+        dirs.migrate().unwrap();
+
+        assert!(dirs.legacy.join("deploy.sh").exists());
+        assert!(!dirs.data.join("deploy.sh").exists());
+        assert!(dirs.data.join("secrets.age").exists());
+        assert_eq!(
+            fs::read_to_string(dirs.config.join(CONFIG_FILE_NAME)).unwrap(),
+            config
+        );
+    }
+
+    #[test]
+    fn nothing_moves_when_a_destination_is_taken() {
+        let dirs = Dirs::new("");
+        fs::write(dirs.data.join("deploy.sh"), "echo other").unwrap();
+
+        let err = dirs.migrate().unwrap_err();
+        assert!(err.to_string().contains("already exists"), "{}", err);
+
+        assert!(dirs.legacy.join(HISTORY_FILE_NAME).exists());
+        assert!(dirs.legacy.join(CONFIG_FILE_NAME).exists());
+        assert!(!dirs.config.join(CONFIG_FILE_NAME).exists());
+    }
+}
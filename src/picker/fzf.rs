@@ -0,0 +1,252 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use super::{Action, BROWSE_BINDINGS, Picker, Selection, is_installed};
+use crate::paths;
+use crate::store::CommandStore;
+
+/// How fzf looks and behaves, from the `[fzf]` section of `config.toml`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FzfOptions {
+    /// Lines or percentage of the terminal taken by the picker
+    pub height: String,
+    /// `default`, `reverse` or `reverse-list`
+    pub layout: String,
+    pub border: String,
+    pub pointer: String,
+    pub prompt: String,
+    /// Position and size of the preview, as for `--preview-window`
+    pub preview_window: String,
+    /// Preview command. `{file}` is replaced with the command's script file and
+    /// `{}` with its name, as usual in fzf. Defaults to bat, or cat without it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preview: Option<String>,
+    /// Extra arguments passed to fzf as is
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Keys accepting the selection with an action, e.g. `ctrl-e = "edit"`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub bind: BTreeMap<String, Action>,
+}
+
+impl Default for FzfOptions {
+    fn default() -> Self {
+        FzfOptions {
+            height: "30%".to_string(),
+            layout: "default".to_string(),
+            border: "rounded".to_string(),
+            pointer: "▶".to_string(),
+            prompt: "Command > ".to_string(),
+            preview_window: "right:60%".to_string(),
+            preview: None,
+            args: Vec::new(),
+            bind: BTreeMap::new(),
+        }
+    }
+}
+
+impl FzfOptions {
+    pub fn is_default(&self) -> bool {
+        *self == FzfOptions::default()
+    }
+}
+
+/// Picks with the external `fzf` binary, previewing scripts with `bat` when
+/// it is installed
+#[derive(Default)]
+pub struct FzfPicker {
+    options: FzfOptions,
+}
+
+impl FzfPicker {
+    pub fn new(options: FzfOptions) -> Self {
+        FzfPicker { options }
+    }
+}
+
+impl Picker for FzfPicker {
+    fn pick(&self, store: &CommandStore, header: &str, multi: bool) -> Result<Vec<String>> {
+        Ok(self.pick_action(store, header, multi)?.names)
+    }
+
+    fn pick_action(&self, store: &CommandStore, header: &str, multi: bool) -> Result<Selection> {
+        select_tasks_with_preview(store, header, multi, &self.options)
+    }
+
+    fn browse(&self, store: &CommandStore, header: &str) -> Result<Selection> {
+        let mut options = self.options.clone();
+
+        for (key, action) in BROWSE_BINDINGS {
+            options.bind.entry(key.to_string()).or_insert(action);
+        }
+
+        // Show the keys under the header, in the order of the actions
+        let mut keys: Vec<(&String, &Action)> = options.bind.iter().collect();
+        keys.sort_by_key(|(_, action)| **action as u8);
+
+        let hints: Vec<String> = std::iter::once("enter: run".to_string())
+            .chain(
+                keys.iter()
+                    .map(|(key, action)| format!("{}: {}", key, action)),
+            )
+            .collect();
+
+        let header = format!("{}\n{}", header, hints.join("  "));
+        select_tasks_with_preview(store, &header, false, &options)
+    }
+}
+
+pub fn is_fzf_available() -> bool {
+    is_installed("fzf")
+}
+
+fn select_with_fzf(
+    items: &[String],
+    header: &str,
+    preview_cmd: Option<&str>,
+    multi: bool,
+    options: &FzfOptions,
+) -> Result<Selection> {
+    if !is_fzf_available() {
+        anyhow::bail!("fzf is not installed or not in PATH");
+    }
+
+    let mut cmd = Command::new("fzf");
+    cmd.arg(format!("--height={}", options.height))
+        .arg(format!("--layout={}", options.layout))
+        .arg(format!("--border={}", options.border))
+        .arg(format!("--header={}", header))
+        .arg(format!("--pointer={}", options.pointer))
+        .arg(format!("--prompt={}", options.prompt));
+
+    if multi {
+        cmd.arg("--multi");
+    }
+
+    if let Some(preview) = preview_cmd {
+        cmd.arg(format!("--preview={}", preview));
+        cmd.arg(format!("--preview-window={}", options.preview_window));
+    }
+
+    // With --expect, fzf prints the key that accepted the selection (or an
+    // empty line for enter) before the selected items
+    let expect = !options.bind.is_empty();
+
+    if expect {
+        let keys: Vec<&str> = options.bind.keys().map(String::as_str).collect();
+        cmd.arg(format!("--expect={}", keys.join(",")));
+    }
+
+    cmd.args(&options.args);
+
+    cmd.stdin(Stdio::piped()).stdout(Stdio::piped());
+
+    let mut child = cmd.spawn().context("Failed to spawn fzf")?;
+
+    if let Some(stdin) = child.stdin.as_mut() {
+        for item in items {
+            writeln!(stdin, "{}", item).context("Failed to write to fzf stdin")?;
+        }
+    }
+
+    let output = child.wait_with_output().context("Failed to wait for fzf")?;
+
+    // Check if user canceled (exit code 130)
+    if !output.status.success() {
+        if output.status.code() == Some(130) {
+            return Ok(Selection::default());
+        }
+        anyhow::bail!("fzf exited with status: {}", output.status);
+    }
+
+    let selected = String::from_utf8(output.stdout).context("Failed to parse fzf output")?;
+    let mut lines = selected.lines();
+
+    let action = if expect {
+        lines
+            .next()
+            .and_then(|key| options.bind.get(key.trim()))
+            .copied()
+    } else {
+        None
+    };
+
+    Ok(Selection {
+        names: lines
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect(),
+        action,
+    })
+}
+
+/// Pick commands with fzf, allowing several to be selected when `multi` is set
+pub fn select_tasks_with_preview(
+    store: &CommandStore,
+    header: &str,
+    multi: bool,
+    options: &FzfOptions,
+) -> Result<Selection> {
+    if !is_fzf_available() {
+        anyhow::bail!("fzf is not installed or not in PATH");
+    }
+
+    let tasks = store.names();
+
+    if tasks.is_empty() {
+        return Ok(Selection::default());
+    }
+
+    // The preview reads `<name>.sh` files, so backends without script files
+    // get a temporary copy of every script
+    let preview_dir = if store.iter().all(|entry| entry.path().is_some()) {
+        None
+    } else {
+        let dir = tempfile::Builder::new()
+            .prefix("preview-")
+            .tempdir_in(paths::cache_dir()?)
+            .context("Failed to create preview directory")?;
+
+        for entry in store.iter() {
+            std::fs::write(
+                dir.path().join(format!("{}.sh", entry.name())),
+                entry.script(),
+            )
+            .context("Failed to write preview file")?;
+        }
+
+        Some(dir)
+    };
+
+    let storage_dir = match &preview_dir {
+        Some(dir) => dir.path(),
+        None => store.root(),
+    };
+
+    let template = match &options.preview {
+        Some(template) => template.as_str(),
+        None if is_installed("bat") => "bat --color=always --style=numbers {file}",
+        None if cfg!(windows) => "type {file}",
+        None => "cat {file}",
+    };
+
+    // fzf quotes the name it puts in place of `{}`, but not the directory
+    let file = format!("{}/{{}}.sh", shell_quote(&storage_dir.to_string_lossy()));
+    let preview_cmd = template.replace("{file}", &file);
+
+    select_with_fzf(&tasks, header, Some(&preview_cmd), multi, options)
+}
+
+/// Quote `text` as a single word for the shell fzf runs the preview with
+fn shell_quote(text: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", text)
+    } else {
+        format!("'{}'", text.replace('\'', "'\\''"))
+    }
+}
@@ -0,0 +1,68 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::io::{BufRead, Write};
+
+use super::Picker;
+use crate::store::CommandStore;
+
+/// Prints a numbered list and reads the chosen numbers from stdin, so it
+/// works in pipes and scripts where no terminal UI can be drawn
+pub struct MenuPicker;
+
+impl Picker for MenuPicker {
+    fn pick(&self, store: &CommandStore, header: &str, multi: bool) -> Result<Vec<String>> {
+        let names = store.names();
+
+        if names.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // The menu goes to stderr so it doesn't mix with the command's output
+        let mut stderr = std::io::stderr();
+        writeln!(stderr, "{}", header.bold())?;
+
+        for (index, name) in names.iter().enumerate() {
+            writeln!(stderr, "{:>3}) {}", index + 1, name)?;
+        }
+
+        if multi {
+            write!(
+                stderr,
+                "Numbers, separated by spaces or commas (empty to cancel): "
+            )?;
+        } else {
+            write!(stderr, "Number (empty to cancel): ")?;
+        }
+        stderr.flush()?;
+
+        let mut answer = String::new();
+        std::io::stdin()
+            .lock()
+            .read_line(&mut answer)
+            .context("Failed to read input")?;
+
+        let mut picked = Vec::new();
+
+        for choice in answer
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|choice| !choice.is_empty())
+        {
+            let name = choice
+                .parse::<usize>()
+                .ok()
+                .and_then(|number| names.get(number.checked_sub(1)?))
+                .with_context(|| format!("Invalid selection '{}'", choice))?;
+
+            if !picked.contains(name) {
+                picked.push(name.clone());
+            }
+        }
+
+        if !multi && picked.len() > 1 {
+            anyhow::bail!("Pick a single command");
+        }
+
+        picked.sort();
+        Ok(picked)
+    }
+}
@@ -0,0 +1,193 @@
+mod fzf;
+mod menu;
+mod select;
+#[cfg(unix)]
+mod skim;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::IsTerminal;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::store::CommandStore;
+
+pub use fzf::{FzfOptions, FzfPicker};
+pub use menu::MenuPicker;
+pub use select::DialoguerPicker;
+#[cfg(unix)]
+pub use skim::SkimPicker;
+
+/// What to do with picked commands, bound to a key in the picker
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Run,
+    Edit,
+    Show,
+    Copy,
+    Delete,
+    Rename,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Action::Run => "run",
+            Action::Edit => "edit",
+            Action::Show => "show",
+            Action::Copy => "copy",
+            Action::Delete => "delete",
+            Action::Rename => "rename",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// Keys bound when browsing commands with a bare `zerp`, on top of enter to
+/// run. `[fzf.bind]` in `config.toml` takes precedence.
+pub const BROWSE_BINDINGS: [(&str, Action); 5] = [
+    ("ctrl-e", Action::Edit),
+    ("ctrl-s", Action::Show),
+    ("ctrl-y", Action::Copy),
+    ("ctrl-d", Action::Delete),
+    ("ctrl-r", Action::Rename),
+];
+
+/// Picked commands, with the action bound to the key that accepted them
+#[derive(Debug, Default)]
+pub struct Selection {
+    pub names: Vec<String>,
+    /// `None` when the pick was accepted with enter
+    pub action: Option<Action>,
+}
+
+/// Interactive selection of stored commands
+pub trait Picker {
+    /// Let the user pick commands from `store`, several when `multi` is set.
+    /// Returns the picked names in list order, or nothing when cancelled.
+    fn pick(&self, store: &CommandStore, header: &str, multi: bool) -> Result<Vec<String>>;
+
+    /// Like `pick`, also reporting the action of a bound key for pickers
+    /// that support key bindings
+    fn pick_action(&self, store: &CommandStore, header: &str, multi: bool) -> Result<Selection> {
+        Ok(Selection {
+            names: self.pick(store, header, multi)?,
+            action: None,
+        })
+    }
+
+    /// Pick a single command to act on with every action bound to a key.
+    /// Pickers without key bindings only pick, which runs the command.
+    fn browse(&self, store: &CommandStore, header: &str) -> Result<Selection> {
+        self.pick_action(store, header, false)
+    }
+
+    /// Let the user pick a single command
+    fn pick_one(&self, store: &CommandStore, header: &str) -> Result<Option<String>> {
+        Ok(self.pick(store, header, false)?.into_iter().next())
+    }
+}
+
+/// The available pickers, selected with `picker` in `config.toml` or `--picker`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PickerKind {
+    /// The numbered menu without a terminal, else fzf when installed, else dialoguer
+    #[default]
+    Auto,
+    /// The external `fzf` binary
+    Fzf,
+    /// skim, built into zerp (Unix only)
+    Skim,
+    /// A fuzzy prompt drawn by dialoguer
+    Dialoguer,
+    /// A numbered list read from stdin, which works without a terminal
+    Menu,
+}
+
+impl PickerKind {
+    pub const ALL: [PickerKind; 5] = [
+        PickerKind::Auto,
+        PickerKind::Fzf,
+        PickerKind::Skim,
+        PickerKind::Dialoguer,
+        PickerKind::Menu,
+    ];
+
+    /// Create the picker, resolving `auto` to what the environment supports
+    pub fn open(self, fzf: &FzfOptions) -> Result<Box<dyn Picker>> {
+        let terminal = std::io::stdin().is_terminal() && std::io::stderr().is_terminal();
+
+        Ok(match self {
+            PickerKind::Auto if !terminal => Box::new(MenuPicker),
+            PickerKind::Auto if fzf::is_fzf_available() => Box::new(FzfPicker::new(fzf.clone())),
+            PickerKind::Auto => Box::new(DialoguerPicker),
+            PickerKind::Fzf => Box::new(FzfPicker::new(fzf.clone())),
+            #[cfg(unix)]
+            PickerKind::Skim => Box::new(SkimPicker),
+            #[cfg(not(unix))]
+            PickerKind::Skim => anyhow::bail!("The skim picker is only available on Unix"),
+            PickerKind::Dialoguer => Box::new(DialoguerPicker),
+            PickerKind::Menu => Box::new(MenuPicker),
+        })
+    }
+}
+
+impl fmt::Display for PickerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PickerKind::Auto => "auto",
+            PickerKind::Fzf => "fzf",
+            PickerKind::Skim => "skim",
+            PickerKind::Dialoguer => "dialoguer",
+            PickerKind::Menu => "menu",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for PickerKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        PickerKind::ALL
+            .into_iter()
+            .find(|kind| kind.to_string() == s.to_lowercase())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown picker '{}', expected one of: auto, fzf, skim, dialoguer, menu",
+                    s
+                )
+            })
+    }
+}
+
+/// Whether `program` is an executable found in `PATH`
+pub fn is_installed(program: &str) -> bool {
+    let Some(paths) = std::env::var_os("PATH") else {
+        return false;
+    };
+
+    std::env::split_paths(&paths).any(|dir| {
+        let candidate = dir.join(program);
+        is_executable(&candidate) || (cfg!(windows) && candidate.with_extension("exe").is_file())
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
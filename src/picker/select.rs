@@ -0,0 +1,43 @@
+use anyhow::Result;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::{FuzzySelect, MultiSelect};
+
+use super::Picker;
+use crate::store::CommandStore;
+
+/// Picks with dialoguer prompts: fuzzy matching for one command, checkboxes
+/// for several
+pub struct DialoguerPicker;
+
+impl Picker for DialoguerPicker {
+    fn pick(&self, store: &CommandStore, header: &str, multi: bool) -> Result<Vec<String>> {
+        let names = store.names();
+
+        if names.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let theme = ColorfulTheme::default();
+
+        let selection = if multi {
+            MultiSelect::with_theme(&theme)
+                .with_prompt(header)
+                .items(&names)
+                .interact_opt()?
+                .unwrap_or_default()
+        } else {
+            FuzzySelect::with_theme(&theme)
+                .with_prompt(header)
+                .default(0)
+                .items(&names)
+                .interact_opt()?
+                .into_iter()
+                .collect()
+        };
+
+        Ok(selection
+            .into_iter()
+            .map(|index| names[index].clone())
+            .collect())
+    }
+}
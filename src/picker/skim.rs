@@ -0,0 +1,58 @@
+use anyhow::Result;
+use skim::prelude::*;
+use std::collections::BTreeMap;
+
+use super::Picker;
+use crate::store::CommandStore;
+
+/// Picks with skim, a fuzzy finder built into zerp, so nothing needs to be
+/// installed. Scripts are previewed from the store, whatever the backend.
+pub struct SkimPicker;
+
+impl Picker for SkimPicker {
+    fn pick(&self, store: &CommandStore, header: &str, multi: bool) -> Result<Vec<String>> {
+        let names = store.names();
+
+        if names.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let scripts: BTreeMap<String, String> = store
+            .iter()
+            .map(|entry| (entry.name().to_string(), entry.script().to_string()))
+            .collect();
+
+        let preview = PreviewCallback::from(move |items: Vec<Arc<dyn SkimItem>>| {
+            items
+                .first()
+                .and_then(|item| scripts.get(item.output().as_ref()))
+                .map(|script| script.lines().map(str::to_string).collect())
+                .unwrap_or_default()
+        });
+
+        let options = SkimOptionsBuilder::default()
+            .height("30%")
+            .multi(multi)
+            .prompt("Command > ")
+            .header(header)
+            .preview_fn(preview)
+            .build()
+            .map_err(|err| anyhow::anyhow!("Invalid skim options: {}", err))?;
+
+        let output = Skim::run_items(options, names)
+            .map_err(|err| anyhow::anyhow!("Failed to run skim: {}", err))?;
+
+        if output.is_abort {
+            return Ok(Vec::new());
+        }
+
+        let mut picked: Vec<String> = output
+            .selected_items
+            .iter()
+            .map(|item| item.item.output().to_string())
+            .collect();
+
+        picked.sort();
+        Ok(picked)
+    }
+}
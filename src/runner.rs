@@ -0,0 +1,998 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+#[cfg(unix)]
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use colored::{Color, ColoredString, Colorize};
+
+use crate::env::{self, GlobalEnv};
+use crate::history::History;
+use crate::metadata::{self, Metadata};
+use crate::secret::{self, SecretStore};
+use crate::store::CommandStore;
+
+/// Delay between attempts when retries are enabled without an explicit delay
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Time given to a timed out command to exit after SIGTERM before SIGKILL
+const KILL_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+const PREFIX_COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Magenta,
+    Color::Yellow,
+    Color::Blue,
+    Color::Green,
+    Color::BrightRed,
+];
+
+/// Options controlling how stored commands are executed
+#[derive(Debug, Default)]
+pub struct RunOptions {
+    /// Working directory overriding the one from the command's metadata
+    pub cwd: Option<String>,
+    /// Environment variables overriding every other source
+    pub env: Vec<(String, String)>,
+    /// Print what would be executed without running anything
+    pub dry_run: bool,
+    /// Kill the command if it runs longer than this
+    pub timeout: Option<Duration>,
+    /// Number of times to retry a failed command
+    pub retries: Option<u32>,
+    /// Delay before the first retry
+    pub retry_delay: Option<Duration>,
+    /// Double the retry delay after every failed attempt
+    pub backoff: bool,
+    /// Run commands that require confirmation without asking
+    pub yes: bool,
+}
+
+/// How often and how quickly a failed command is retried
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub delay: Duration,
+    pub backoff: bool,
+}
+
+impl RetryPolicy {
+    pub fn new(retries: Option<u32>, delay: Option<Duration>, backoff: bool) -> Self {
+        RetryPolicy {
+            retries: retries.unwrap_or(0),
+            delay: delay.unwrap_or(DEFAULT_RETRY_DELAY),
+            backoff,
+        }
+    }
+}
+
+/// How a single attempt or a whole job ended
+#[derive(Debug, Clone, Copy)]
+pub enum Outcome {
+    Exited(ExitStatus),
+    TimedOut(Duration),
+}
+
+impl Outcome {
+    pub fn success(&self) -> bool {
+        matches!(self, Outcome::Exited(status) if status.success())
+    }
+
+    /// Exit code a shell would report: the process's own code, 128 + signal
+    /// number when it was killed by a signal, or 124 (like `timeout(1)`) when
+    /// zerp killed it after a timeout.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Outcome::Exited(status) => {
+                if let Some(code) = status.code() {
+                    return code;
+                }
+
+                #[cfg(unix)]
+                {
+                    use std::os::unix::process::ExitStatusExt;
+                    if let Some(signal) = status.signal() {
+                        return 128 + signal;
+                    }
+                }
+
+                1
+            }
+            Outcome::TimedOut(_) => 124,
+        }
+    }
+}
+
+/// Error for a command that ran but did not succeed, carrying the exit code
+/// zerp itself should exit with.
+#[derive(Debug)]
+pub struct CommandFailed {
+    pub message: String,
+    pub code: i32,
+}
+
+impl std::fmt::Display for CommandFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for CommandFailed {}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Exited(status) if status.success() => write!(f, "ok"),
+            Outcome::Exited(status) => match status.code() {
+                Some(code) => write!(f, "exit {}", code),
+                None => write!(f, "killed"),
+            },
+            Outcome::TimedOut(_) => write!(f, "timed out"),
+        }
+    }
+}
+
+/// Settings shared by every run, usually built from the configuration
+/// with `CliConfig::run_context`
+#[derive(Default)]
+pub struct RunContext {
+    /// Environment applied to every command
+    pub env: GlobalEnv,
+    /// Store used to fill in `{{secret:name}}` references
+    pub secrets: Option<SecretStore>,
+    /// Commands containing any of these must be confirmed before running
+    pub dangerous_patterns: Vec<String>,
+    /// Where the last run of every command is recorded
+    pub history: Option<History>,
+}
+
+impl RunContext {
+    /// Record a finished run in the history, if there is one
+    pub fn record_run(&self, name: &str, outcome: &Outcome) -> Result<()> {
+        match &self.history {
+            Some(history) => history.record(name, outcome),
+            None => Ok(()),
+        }
+    }
+
+    /// Move the history of a renamed command, if there is one
+    pub fn rename_runs(&self, current_name: &str, new_name: &str) -> Result<()> {
+        match &self.history {
+            Some(history) => history.rename(current_name, new_name),
+            None => Ok(()),
+        }
+    }
+
+    /// Drop the history of a deleted command, if there is one
+    pub fn forget_runs(&self, name: &str) -> Result<()> {
+        match &self.history {
+            Some(history) => history.forget(name),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Output stream a captured line was written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// A stored command scheduled for execution
+pub struct Job {
+    pub name: String,
+    /// The stored script file, for backends keeping one file per command
+    pub path: Option<PathBuf>,
+    /// The script as stored, with secret references left in place
+    pub script: String,
+    pub cwd: Option<PathBuf>,
+    pub env: BTreeMap<String, String>,
+    /// Copy of the script that is executed instead of `path`, either with
+    /// secrets filled in or because the backend has no script files
+    pub resolved_script: Option<ResolvedScript>,
+    pub timeout: Option<Duration>,
+    pub retry: RetryPolicy,
+    /// Why the job must be confirmed before running, if it must
+    pub confirm: Option<String>,
+    secrets_pending: bool,
+}
+
+impl Job {
+    /// Resolve everything needed to execute the stored command called `name`:
+    /// its working directory, environment and retry policy. Secrets are
+    /// resolved separately by `resolve_secrets`.
+    pub fn prepare(
+        store: &CommandStore,
+        name: &str,
+        options: &RunOptions,
+        context: &RunContext,
+    ) -> Result<Job> {
+        let entry = store.require(name)?;
+        let path = entry.path().map(Path::to_path_buf);
+        let metadata = entry.metadata()?;
+        let cwd = metadata::resolve_cwd(options.cwd.as_deref(), metadata)?;
+        let env = env::resolve_env(&context.env, metadata, cwd.as_deref(), &options.env)?;
+        let script = entry.script().to_string();
+        let confirm = confirmation_reason(metadata, &script, &context.dangerous_patterns);
+
+        // Secrets are only filled in by `resolve_secrets`, right before
+        // running, so they never show up in dry runs, confirmation prompts or
+        // anywhere in storage.
+        let secrets_pending = !secret::references(&script).is_empty()
+            || env
+                .values()
+                .any(|value| !secret::references(value).is_empty());
+
+        let resolved_script = match path {
+            None if !secrets_pending => Some(ResolvedScript::new(&script)?),
+            _ => None,
+        };
+
+        let timeout = options.timeout.or(metadata.timeout()?);
+        let retry = RetryPolicy::new(
+            options.retries.or(metadata.retries),
+            options.retry_delay.or(metadata.retry_delay()?),
+            options.backoff || metadata.backoff,
+        );
+
+        Ok(Job {
+            name: name.to_string(),
+            path,
+            script,
+            cwd,
+            env,
+            resolved_script,
+            timeout,
+            retry,
+            confirm,
+            secrets_pending,
+        })
+    }
+
+    /// Fill in the secrets the script and environment reference, which may
+    /// prompt for the passphrase. Does nothing when there are none, or when
+    /// they were already resolved.
+    pub fn resolve_secrets(&mut self, context: &RunContext) -> Result<()> {
+        if !self.secrets_pending {
+            return Ok(());
+        }
+
+        let secrets = context
+            .secrets
+            .as_ref()
+            .context("Secrets are not available")?
+            .load()?;
+
+        for value in self.env.values_mut() {
+            *value = secret::resolve(value, &secrets)?;
+        }
+
+        let resolved = secret::resolve(&self.script, &secrets)?;
+        self.resolved_script = Some(ResolvedScript::new(&resolved)?);
+        self.secrets_pending = false;
+
+        Ok(())
+    }
+
+    /// Whether running the job needs secrets that are not resolved yet
+    pub fn needs_secrets(&self) -> bool {
+        self.secrets_pending
+    }
+
+    /// Build the process that executes this job
+    pub fn command(&self) -> Command {
+        let script = match &self.resolved_script {
+            Some(resolved) => Some(resolved.path()),
+            None => self.path.clone(),
+        };
+
+        let mut command = match script {
+            Some(script) => {
+                // Scripts choose their interpreter with a shebang, like an
+                // executable would, and run with `sh` otherwise.
+                let mut command = match parse_shebang(&self.script) {
+                    Some((interpreter, argument)) => {
+                        let mut command = Command::new(interpreter);
+                        command.args(argument);
+                        command
+                    }
+                    None => Command::new("sh"),
+                };
+                command.arg(script);
+                command
+            }
+            None => {
+                let mut command = Command::new("sh");
+                command.arg("-c").arg(&self.script);
+                command
+            }
+        };
+        command.envs(&self.env);
+
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(cwd);
+        }
+
+        if let Some(resolved) = &self.resolved_script {
+            resolved.pass_to(&mut command);
+        }
+
+        command
+    }
+
+    /// Spawn the job's command. With a timeout, the command leads its own
+    /// process group, so that a timeout kills everything it started. When it
+    /// shares zerp's terminal (`interactive`), the group is made the
+    /// terminal's foreground one until the command is done, so that it can
+    /// still read from it.
+    fn spawn(&self, command: &mut Command, interactive: bool) -> Result<Running> {
+        if self.secrets_pending {
+            anyhow::bail!("Secrets of {} are not resolved", self.name);
+        }
+
+        let detached = cfg!(unix) && self.timeout.is_some();
+
+        #[cfg(unix)]
+        let terminal = (detached && interactive && in_foreground()).then_some(Terminal);
+
+        #[cfg(unix)]
+        if detached {
+            use std::os::unix::process::CommandExt;
+            command.process_group(0);
+
+            if terminal.is_some() {
+                // SAFETY: `take_terminal` only calls async-signal-safe
+                // functions, so it may run in the forked child before exec.
+                unsafe { command.pre_exec(take_terminal) };
+            }
+        }
+
+        #[cfg(not(unix))]
+        let _ = interactive;
+
+        // Signals sent to zerp alone, like `kill` or Ctrl-C when the command
+        // is not in the foreground, are passed on to it by `wait`
+        let signals = detached.then(ForwardSignals::install);
+
+        let child = command
+            .spawn()
+            .with_context(|| format!("Failed to execute {}", self.name))?;
+
+        Ok(Running {
+            child,
+            detached,
+            _signals: signals,
+            #[cfg(unix)]
+            _terminal: terminal,
+        })
+    }
+
+    /// Run the job with inherited stdio, retrying it according to its policy
+    pub fn run(&self) -> Result<Outcome> {
+        self.run_attempts(
+            || {
+                let mut running = self.spawn(&mut self.command(), true)?;
+                self.wait(&mut running)
+            },
+            |message| eprintln!("{}", message.yellow()),
+        )
+    }
+
+    /// Run the job with its output captured instead of inherited, passing
+    /// every line to `on_line` (retry notices go to stderr). Stdin is closed.
+    pub fn run_captured(
+        &self,
+        on_line: impl Fn(Stream, String) + Clone + Send + 'static,
+    ) -> Result<Outcome> {
+        self.run_attempts(
+            || run_piped_once(self, on_line.clone()),
+            |message| on_line(Stream::Stderr, message),
+        )
+    }
+
+    /// Run attempts until one succeeds or the retries are exhausted
+    fn run_attempts(
+        &self,
+        mut attempt: impl FnMut() -> Result<Outcome>,
+        log: impl Fn(String),
+    ) -> Result<Outcome> {
+        let attempts = self.retry.retries + 1;
+        let mut delay = self.retry.delay;
+
+        let mut number = 1;
+
+        loop {
+            let outcome = attempt()?;
+
+            if outcome.success() {
+                return Ok(outcome);
+            }
+
+            if number >= attempts {
+                if attempts > 1 {
+                    log(format!(
+                        "Attempt {}/{} failed ({}), giving up",
+                        number, attempts, outcome
+                    ));
+                }
+                return Ok(outcome);
+            }
+
+            log(format!(
+                "Attempt {}/{} failed ({}), retrying in {}",
+                number,
+                attempts,
+                outcome,
+                humantime::format_duration(delay)
+            ));
+
+            thread::sleep(delay);
+
+            if self.retry.backoff {
+                delay *= 2;
+            }
+
+            number += 1;
+        }
+    }
+
+    /// Wait for the child to exit, killing its process group on timeout
+    fn wait(&self, running: &mut Running) -> Result<Outcome> {
+        let child = &mut running.child;
+
+        let Some(timeout) = self.timeout else {
+            let status = child.wait().context("Failed to wait for command")?;
+            return Ok(Outcome::Exited(status));
+        };
+
+        let deadline = Instant::now() + timeout;
+        let mut forwarded = false;
+
+        loop {
+            if let Some(status) = child.try_wait().context("Failed to wait for command")? {
+                return Ok(Outcome::Exited(status));
+            }
+
+            if running.detached && !forwarded {
+                forwarded = forward_pending_signal(child);
+            }
+
+            if Instant::now() >= deadline {
+                kill_process_group(child);
+                return Ok(Outcome::TimedOut(timeout));
+            }
+
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Describe the job instead of running it
+    pub fn print_dry_run(&self) {
+        println!("{} {}", "Command:".bold(), self.name.green());
+        match &self.path {
+            Some(path) => println!("  script: {}", path.display()),
+            None => println!("  script: (no script file, run from a temp copy)"),
+        }
+
+        match &self.cwd {
+            Some(cwd) => println!("  cwd:    {}", cwd.display()),
+            None => println!("  cwd:    (current directory)"),
+        }
+
+        if let Some(reason) = &self.confirm {
+            println!("  confirm: required ({})", reason);
+        }
+
+        if let Some(timeout) = self.timeout {
+            println!("  timeout: {}", humantime::format_duration(timeout));
+        }
+
+        if self.retry.retries > 0 {
+            println!(
+                "  retries: {} (delay {}{})",
+                self.retry.retries,
+                humantime::format_duration(self.retry.delay),
+                if self.retry.backoff {
+                    ", exponential backoff"
+                } else {
+                    ""
+                }
+            );
+        }
+
+        if self.env.is_empty() {
+            println!("  env:    (inherited only)");
+        } else {
+            println!("  env:");
+            for (key, value) in &self.env {
+                println!("    {}={}", key, value);
+            }
+        }
+    }
+}
+
+/// Interpreter and optional argument from a script's `#!` line
+fn parse_shebang(script: &str) -> Option<(String, Option<String>)> {
+    let shebang = script.lines().next()?.strip_prefix("#!")?.trim();
+    let (interpreter, argument) = match shebang.split_once(char::is_whitespace) {
+        Some((interpreter, argument)) => (interpreter, Some(argument.trim().to_string())),
+        None => (shebang, None),
+    };
+
+    if interpreter.is_empty() {
+        return None;
+    }
+
+    Some((interpreter.to_string(), argument))
+}
+
+/// A spawned job's process
+struct Running {
+    child: Child,
+    /// Whether the child leads its own process group
+    detached: bool,
+    _signals: Option<ForwardSignals>,
+    #[cfg(unix)]
+    _terminal: Option<Terminal>,
+}
+
+/// Gives the terminal back to zerp's process group once dropped, after a
+/// command's group was made the foreground one
+#[cfg(unix)]
+struct Terminal;
+
+#[cfg(unix)]
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = take_terminal();
+    }
+}
+
+/// Whether zerp's process group is the foreground group of the terminal on
+/// stdin
+#[cfg(unix)]
+fn in_foreground() -> bool {
+    // SAFETY: both calls only query the process and terminal state.
+    unsafe { libc::tcgetpgrp(libc::STDIN_FILENO) == libc::getpgrp() }
+}
+
+/// Make the caller's process group the foreground group of the terminal on
+/// stdin. SIGTTOU is ignored meanwhile: it would stop a background group
+/// doing so.
+#[cfg(unix)]
+fn take_terminal() -> std::io::Result<()> {
+    // SAFETY: signal(), getpgrp() and tcsetpgrp() are async-signal-safe, and
+    // the SIGTTOU disposition is restored right after.
+    let result = unsafe {
+        let previous = libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+        let result = libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp());
+        libc::signal(libc::SIGTTOU, previous);
+        result
+    };
+
+    if result == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Signal received by zerp while detached commands run, 0 for none
+#[cfg(unix)]
+static PENDING_SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+/// Number of detached commands running, and the SIGINT and SIGTERM handlers
+/// to restore once there are none
+#[cfg(unix)]
+static DETACHED: Mutex<(usize, [libc::sighandler_t; 2])> = Mutex::new((0, [0; 2]));
+
+#[cfg(unix)]
+const FORWARDED_SIGNALS: [libc::c_int; 2] = [libc::SIGINT, libc::SIGTERM];
+
+#[cfg(unix)]
+extern "C" fn record_signal(signal: libc::c_int) {
+    PENDING_SIGNAL.store(signal, Ordering::SeqCst);
+}
+
+/// Records SIGINT and SIGTERM while detached commands run, so they can be
+/// forwarded to their process groups. Once the last one is done, the
+/// previous handlers are restored and a recorded signal is raised again, so
+/// zerp still ends the way it would have.
+struct ForwardSignals;
+
+impl ForwardSignals {
+    fn install() -> Self {
+        #[cfg(unix)]
+        {
+            let mut detached = DETACHED.lock().unwrap_or_else(|err| err.into_inner());
+
+            if detached.0 == 0 {
+                for (signal, previous) in FORWARDED_SIGNALS.iter().zip(detached.1.iter_mut()) {
+                    let handler = record_signal as extern "C" fn(libc::c_int);
+                    // SAFETY: the handler only stores to an atomic, which is
+                    // async-signal-safe.
+                    *previous = unsafe { libc::signal(*signal, handler as libc::sighandler_t) };
+                }
+            }
+
+            detached.0 += 1;
+        }
+
+        ForwardSignals
+    }
+}
+
+impl Drop for ForwardSignals {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        {
+            let mut detached = DETACHED.lock().unwrap_or_else(|err| err.into_inner());
+            detached.0 -= 1;
+
+            if detached.0 > 0 {
+                return;
+            }
+
+            for (signal, previous) in FORWARDED_SIGNALS.iter().zip(detached.1) {
+                // SAFETY: restores the handler that was in place before
+                // `install`.
+                unsafe { libc::signal(*signal, previous) };
+            }
+
+            let signal = PENDING_SIGNAL.swap(0, Ordering::SeqCst);
+            if signal != 0 {
+                // SAFETY: raise() only sends a signal to zerp itself, now
+                // handled as it was before the commands ran.
+                unsafe { libc::raise(signal) };
+            }
+        }
+    }
+}
+
+/// Send a signal received by zerp to the child's process group, returning
+/// whether there was one
+fn forward_pending_signal(child: &Child) -> bool {
+    #[cfg(unix)]
+    {
+        let signal = PENDING_SIGNAL.load(Ordering::SeqCst);
+
+        if signal != 0 {
+            // SAFETY: kill() only sends a signal; the group id comes from a
+            // child we spawned as a group leader.
+            unsafe { libc::kill(-(child.id() as libc::pid_t), signal) };
+            return true;
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = child;
+
+    false
+}
+
+/// Terminate a child and every process in its group
+fn kill_process_group(child: &mut Child) {
+    #[cfg(unix)]
+    {
+        let group = -(child.id() as libc::pid_t);
+
+        // SAFETY: kill() only sends a signal; the group id comes from a child
+        // we spawned as a group leader.
+        unsafe { libc::kill(group, libc::SIGTERM) };
+
+        let deadline = Instant::now() + KILL_GRACE_PERIOD;
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = child.try_wait() {
+                // The leader is gone, make sure nothing it started lingers.
+                // SAFETY: as above, only a signal to our own child's group.
+                unsafe { libc::kill(group, libc::SIGKILL) };
+                return;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+
+        // SAFETY: as above, only a signal to our own child's group.
+        unsafe { libc::kill(group, libc::SIGKILL) };
+    }
+
+    #[cfg(not(unix))]
+    let _ = child.kill();
+
+    let _ = child.wait();
+}
+
+struct JobResult {
+    name: String,
+    status: Result<Outcome>,
+    duration: Duration,
+}
+
+/// Run several commands concurrently, prefixing every line of their output
+/// with the command name, and print a summary table once all have finished.
+/// Finished runs are recorded in the context's history.
+pub fn run_parallel(jobs: Vec<Job>, max_jobs: Option<usize>, context: &RunContext) -> Result<()> {
+    if jobs.is_empty() {
+        return Ok(());
+    }
+
+    let total = jobs.len();
+    let width = jobs
+        .iter()
+        .map(|job| job.name.len())
+        .max()
+        .unwrap_or(0)
+        .max("COMMAND".len());
+    let workers = max_jobs.unwrap_or(total).clamp(1, total);
+
+    let queue: VecDeque<(usize, Job)> = jobs.into_iter().enumerate().collect();
+    let queue = Arc::new(Mutex::new(queue));
+    let results = Arc::new(Mutex::new(Vec::with_capacity(total)));
+
+    let handles: Vec<_> = (0..workers)
+        .map(|_| {
+            let queue = Arc::clone(&queue);
+            let results = Arc::clone(&results);
+
+            thread::spawn(move || {
+                loop {
+                    let next = queue.lock().unwrap().pop_front();
+                    let Some((index, job)) = next else {
+                        break;
+                    };
+
+                    let prefix = format!("{:<width$} |", job.name)
+                        .color(PREFIX_COLORS[index % PREFIX_COLORS.len()]);
+
+                    let started = Instant::now();
+                    let status = run_prefixed(&job, &prefix);
+
+                    if let Err(err) = &status {
+                        eprintln!("{} {}", prefix, format!("{:#}", err).red());
+                    }
+
+                    results.lock().unwrap().push((
+                        index,
+                        JobResult {
+                            name: job.name,
+                            status,
+                            duration: started.elapsed(),
+                        },
+                    ));
+                }
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle
+            .join()
+            .map_err(|_| anyhow::anyhow!("Worker thread panicked"))?;
+    }
+
+    let mut results = std::mem::take(&mut *results.lock().unwrap());
+    results.sort_by_key(|(index, _)| *index);
+
+    // Recorded one after the other, as each record rewrites the history file
+    for (_, result) in &results {
+        if let Ok(outcome) = &result.status
+            && let Err(err) = context.record_run(&result.name, outcome)
+        {
+            eprintln!("{} {:#}", "Failed to record run:".yellow(), err);
+        }
+    }
+
+    let failed = print_summary(results.iter().map(|(_, result)| result), width);
+
+    if failed > 0 {
+        let code = results
+            .iter()
+            .find_map(|(_, result)| match &result.status {
+                Ok(outcome) if !outcome.success() => Some(outcome.exit_code()),
+                Ok(_) => None,
+                Err(_) => Some(1),
+            })
+            .unwrap_or(1);
+
+        return Err(CommandFailed {
+            message: format!("{} of {} commands failed", failed, total),
+            code,
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+fn run_prefixed(job: &Job, prefix: &ColoredString) -> Result<Outcome> {
+    let print = {
+        let prefix = prefix.clone();
+        move |stream, line: String| match stream {
+            Stream::Stdout => println!("{} {}", prefix, line),
+            Stream::Stderr => eprintln!("{} {}", prefix, line),
+        }
+    };
+
+    job.run_attempts(
+        || run_piped_once(job, print.clone()),
+        |message| eprintln!("{} {}", prefix, message.yellow()),
+    )
+}
+
+/// Run a single attempt with stdout and stderr passed to `on_line` line by line
+fn run_piped_once(
+    job: &Job,
+    on_line: impl Fn(Stream, String) + Clone + Send + 'static,
+) -> Result<Outcome> {
+    let mut command = job.command();
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    // Stdin is closed, so the command can leave the terminal's group
+    let mut running = job.spawn(&mut command, false)?;
+    let child = &mut running.child;
+
+    let stdout = child.stdout.take().context("Failed to capture stdout")?;
+    let stderr = child.stderr.take().context("Failed to capture stderr")?;
+
+    let on_out = on_line.clone();
+    let out = thread::spawn(move || forward_lines(stdout, Stream::Stdout, on_out));
+    let err = thread::spawn(move || forward_lines(stderr, Stream::Stderr, on_line));
+
+    let outcome = job.wait(&mut running)?;
+
+    let _ = out.join();
+    let _ = err.join();
+
+    Ok(outcome)
+}
+
+fn forward_lines(stream: impl Read, source: Stream, on_line: impl Fn(Stream, String)) {
+    for line in BufReader::new(stream)
+        .lines()
+        .map_while(std::result::Result::ok)
+    {
+        on_line(source, line);
+    }
+}
+
+/// Print the aggregate status table and return the number of failures
+fn print_summary<'a>(results: impl Iterator<Item = &'a JobResult>, width: usize) -> usize {
+    let mut failed = 0;
+
+    println!();
+    println!("{:<width$}  {:<10}  TIME", "COMMAND", "STATUS");
+
+    for result in results {
+        let status = match &result.status {
+            Ok(outcome) if outcome.success() => outcome.to_string().green(),
+            Ok(outcome) => {
+                failed += 1;
+                outcome.to_string().red()
+            }
+            Err(_) => {
+                failed += 1;
+                "error".red()
+            }
+        };
+
+        println!(
+            "{:<width$}  {:<10}  {:.1}s",
+            result.name,
+            status,
+            result.duration.as_secs_f64()
+        );
+    }
+
+    failed
+}
+
+/// Why a command must be confirmed before running, if it must
+fn confirmation_reason(
+    metadata: &Metadata,
+    script: &str,
+    dangerous_patterns: &[String],
+) -> Option<String> {
+    if metadata.confirm {
+        return Some("marked with confirm = true".to_string());
+    }
+
+    let script = script.to_lowercase();
+
+    dangerous_patterns
+        .iter()
+        .find(|pattern| !pattern.is_empty() && script.contains(&pattern.to_lowercase()))
+        .map(|pattern| format!("matches dangerous pattern '{}'", pattern))
+}
+
+/// Write a script to a temp file deleted once the returned path is dropped
+/// A script written out to be executed. On unix the file has no name, so
+/// the secrets it may hold are never left behind, even when zerp is killed:
+/// the command reads it through `/dev/fd`. Elsewhere it is a temp file
+/// deleted once dropped.
+pub struct ResolvedScript {
+    #[cfg(unix)]
+    file: std::fs::File,
+    #[cfg(not(unix))]
+    path: tempfile::TempPath,
+}
+
+impl ResolvedScript {
+    fn new(script: &str) -> Result<Self> {
+        #[cfg(unix)]
+        {
+            let mut file = tempfile::tempfile().context("Failed to create temp file")?;
+            file.write_all(script.as_bytes())
+                .context("Failed to write to temp file")?;
+            Ok(ResolvedScript { file })
+        }
+
+        #[cfg(not(unix))]
+        {
+            let mut file = tempfile::Builder::new()
+                .prefix("zerp-")
+                .suffix(".sh")
+                .tempfile()
+                .context("Failed to create temp file")?;
+            file.write_all(script.as_bytes())
+                .context("Failed to write to temp file")?;
+            Ok(ResolvedScript {
+                path: file.into_temp_path(),
+            })
+        }
+    }
+
+    /// Path the command reads the script from
+    pub fn path(&self) -> PathBuf {
+        #[cfg(unix)]
+        {
+            use std::os::fd::AsRawFd;
+            PathBuf::from(format!("/dev/fd/{}", self.file.as_raw_fd()))
+        }
+
+        #[cfg(not(unix))]
+        {
+            self.path.to_path_buf()
+        }
+    }
+
+    /// Let `command` open the script at `path`
+    fn pass_to(&self, command: &mut Command) {
+        #[cfg(unix)]
+        {
+            use std::io::{Seek, SeekFrom};
+            use std::os::fd::AsRawFd;
+            use std::os::unix::process::CommandExt;
+
+            // Some systems share the offset with the command's copy of the
+            // file, so every attempt starts from the beginning again
+            let _ = (&self.file).seek(SeekFrom::Start(0));
+
+            let fd = self.file.as_raw_fd();
+
+            // SAFETY: the closure runs in the forked child before exec and
+            // only calls fcntl, which is async-signal-safe. Clearing
+            // FD_CLOEXEC there keeps the file open in the command alone.
+            unsafe {
+                command.pre_exec(move || {
+                    if libc::fcntl(fd, libc::F_SETFD, 0) == -1 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+        }
+
+        #[cfg(not(unix))]
+        let _ = command;
+    }
+}
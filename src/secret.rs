@@ -0,0 +1,297 @@
+use age::secrecy::{ExposeSecret, SecretString};
+use anyhow::{Context, Result};
+use std::cell::OnceCell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Encrypted file holding every secret, in the data directory
+const SECRETS_FILE: &str = "secrets.age";
+
+/// Default location of the age identity used instead of a passphrase
+pub const DEFAULT_KEY_FILE: &str = "secrets.key";
+
+/// Environment variable providing the passphrase non-interactively
+const PASSPHRASE_ENV: &str = "ZERP_SECRETS_PASSPHRASE";
+
+const REFERENCE_START: &str = "{{secret:";
+const REFERENCE_END: &str = "}}";
+/// Shown in place of a secret's value
+const SECRET_MASK: &str = "********";
+
+/// Secrets encrypted at rest with either an age X25519 key file or a passphrase.
+///
+/// The key file is used whenever it exists, otherwise the passphrase is read
+/// from `ZERP_SECRETS_PASSPHRASE` or prompted for.
+pub struct SecretStore {
+    path: PathBuf,
+    key_file: PathBuf,
+    passphrase: OnceCell<SecretString>,
+}
+
+impl SecretStore {
+    pub fn new(data_dir: &Path, key_file: Option<PathBuf>) -> Self {
+        SecretStore {
+            path: data_dir.join(SECRETS_FILE),
+            key_file: key_file.unwrap_or_else(|| data_dir.join(DEFAULT_KEY_FILE)),
+            passphrase: OnceCell::new(),
+        }
+    }
+
+    /// Decrypt and return all secrets
+    pub fn load(&self) -> Result<BTreeMap<String, String>> {
+        if !self.path.exists() {
+            return Ok(BTreeMap::new());
+        }
+
+        let ciphertext = fs::read(&self.path).context("Failed to read secrets file")?;
+
+        let plaintext = if self.key_file.exists() {
+            age::decrypt(&self.read_identity()?, &ciphertext)
+        } else {
+            let passphrase = self.passphrase(false)?;
+            age::decrypt(&age::scrypt::Identity::new(passphrase), &ciphertext)
+        }
+        .context("Failed to decrypt secrets (wrong passphrase or key?)")?;
+
+        let content = String::from_utf8(plaintext).context("Secrets file is corrupted")?;
+        toml::from_str(&content).context("Secrets file is corrupted")
+    }
+
+    /// Encrypt and persist all secrets
+    pub fn save(&self, secrets: &BTreeMap<String, String>) -> Result<()> {
+        let plaintext = toml::to_string(secrets).context("Failed to serialize secrets")?;
+
+        let ciphertext = if self.key_file.exists() {
+            age::encrypt(&self.read_identity()?.to_public(), plaintext.as_bytes())
+        } else {
+            let passphrase = self.passphrase(!self.path.exists())?;
+            age::encrypt(
+                &age::scrypt::Recipient::new(passphrase),
+                plaintext.as_bytes(),
+            )
+        }
+        .context("Failed to encrypt secrets")?;
+
+        write_private(&self.path, &ciphertext).context("Failed to write secrets file")
+    }
+
+    pub fn set(&self, name: &str, value: String) -> Result<()> {
+        validate_name(name)?;
+
+        let mut secrets = self.load()?;
+        secrets.insert(name.to_string(), value);
+        self.save(&secrets)
+    }
+
+    pub fn get(&self, name: &str) -> Result<String> {
+        self.load()?
+            .remove(name)
+            .with_context(|| format!("Secret '{}' not found", name))
+    }
+
+    pub fn remove(&self, name: &str) -> Result<()> {
+        let mut secrets = self.load()?;
+
+        if secrets.remove(name).is_none() {
+            anyhow::bail!("Secret '{}' not found", name);
+        }
+
+        self.save(&secrets)
+    }
+
+    /// Generate an X25519 key file, re-encrypting existing secrets with it
+    pub fn keygen(&self) -> Result<&Path> {
+        if self.key_file.exists() {
+            anyhow::bail!("Key file already exists: {}", self.key_file.display());
+        }
+
+        let secrets = self.load()?;
+        let identity = age::x25519::Identity::generate();
+        let content = format!(
+            "# public key: {}\n{}\n",
+            identity.to_public(),
+            identity.to_string().expose_secret()
+        );
+
+        write_private(&self.key_file, content.as_bytes()).context("Failed to write key file")?;
+
+        if !secrets.is_empty() {
+            self.save(&secrets)?;
+        }
+
+        Ok(&self.key_file)
+    }
+
+    /// Passphrase for the store, asked for at most once per invocation
+    fn passphrase(&self, confirm: bool) -> Result<SecretString> {
+        if let Some(passphrase) = self.passphrase.get() {
+            return Ok(passphrase.clone());
+        }
+
+        let passphrase = read_passphrase(confirm)?;
+        Ok(self.passphrase.get_or_init(|| passphrase).clone())
+    }
+
+    fn read_identity(&self) -> Result<age::x25519::Identity> {
+        let content = fs::read_to_string(&self.key_file)
+            .with_context(|| format!("Failed to read key file {}", self.key_file.display()))?;
+
+        let line = content
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'))
+            .context("Key file does not contain an identity")?;
+
+        age::x25519::Identity::from_str(line)
+            .map_err(|err| anyhow::anyhow!("Invalid key file: {}", err))
+    }
+}
+
+/// Names of the secrets referenced as `{{secret:name}}` in `text`
+pub fn references(text: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find(REFERENCE_START) {
+        rest = &rest[start + REFERENCE_START.len()..];
+
+        if let Some(end) = rest.find(REFERENCE_END) {
+            names.push(rest[..end].to_string());
+            rest = &rest[end + REFERENCE_END.len()..];
+        }
+    }
+
+    names
+}
+
+/// Replace every `{{secret:name}}` reference in `text` with its value
+pub fn resolve(text: &str, secrets: &BTreeMap<String, String>) -> Result<String> {
+    let mut resolved = text.to_string();
+
+    for name in references(text) {
+        let value = secrets
+            .get(&name)
+            .with_context(|| format!("Secret '{}' is referenced but not set", name))?;
+
+        resolved = resolved.replace(
+            &format!("{}{}{}", REFERENCE_START, name, REFERENCE_END),
+            value,
+        );
+    }
+
+    Ok(resolved)
+}
+
+/// Replace every `{{secret:name}}` reference in `text` with a mask, to show
+/// the text as it will run without revealing any secret
+pub fn mask(text: &str) -> String {
+    let mut masked = text.to_string();
+
+    for name in references(text) {
+        masked = masked.replace(
+            &format!("{}{}{}", REFERENCE_START, name, REFERENCE_END),
+            SECRET_MASK,
+        );
+    }
+
+    masked
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+
+    if !valid {
+        anyhow::bail!(
+            "Invalid secret name '{}': use letters, digits, '_', '-' or '.'",
+            name
+        );
+    }
+
+    Ok(())
+}
+
+fn read_passphrase(confirm: bool) -> Result<SecretString> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(SecretString::from(passphrase));
+    }
+
+    let mut prompt = dialoguer::Password::new().with_prompt("Secrets passphrase");
+
+    if confirm {
+        prompt = prompt.with_confirmation("Confirm passphrase", "Passphrases do not match");
+    }
+
+    let passphrase = prompt.interact().context("Failed to read passphrase")?;
+    Ok(SecretString::from(passphrase))
+}
+
+/// Write a file readable only by the current user
+fn write_private(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    use std::io::Write;
+    options.open(path)?.write_all(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secrets() -> BTreeMap<String, String> {
+        BTreeMap::from([
+            ("token".to_string(), "hunter2".to_string()),
+            ("db.pass".to_string(), "s3cret".to_string()),
+        ])
+    }
+
+    #[test]
+    fn finds_references_in_order() {
+        let text =
+            "curl -H {{secret:token}} {{env:HOME}} {{secret:db.pass}}\necho {{secret:token}}";
+        assert_eq!(references(text), ["token", "db.pass", "token"]);
+    }
+
+    #[test]
+    fn unterminated_reference_is_ignored() {
+        assert!(references("echo {{secret:token").is_empty());
+        assert!(references("echo {{token}}").is_empty());
+    }
+
+    #[test]
+    fn resolves_every_reference() {
+        let text = "login {{secret:token}} {{secret:db.pass}} {{secret:token}}";
+        assert_eq!(
+            resolve(text, &secrets()).unwrap(),
+            "login hunter2 s3cret hunter2"
+        );
+    }
+
+    #[test]
+    fn unknown_secret_is_an_error() {
+        let err = resolve("echo {{secret:missing}}", &secrets()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Secret 'missing' is referenced but not set"
+        );
+    }
+
+    #[test]
+    fn masks_without_revealing() {
+        assert_eq!(
+            mask("login {{secret:token}} {{secret:missing}} {{name}}"),
+            "login ******** ******** {{name}}"
+        );
+    }
+}
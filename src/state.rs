@@ -1,66 +1,93 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
 use colored::Colorize;
-use std::io::Write;
+use std::io::{IsTerminal, Write};
+
+use crate::draft::Draft;
+use zerp::lint;
+use zerp::metadata::Metadata;
+use zerp::picker::{Action, DialoguerPicker, Picker};
+use zerp::runner::{self, CommandFailed, Job, Outcome, RunContext, RunOptions};
+use zerp::secret;
+use zerp::store::CommandStore;
+use zerp::template;
 
 pub struct State {
     editor: String,
-    storage: PathBuf,
-    tasks: Vec<String>,
+    store: CommandStore,
+    context: RunContext,
+    lint_on_save: bool,
+    default_template: Option<String>,
+    picker: Box<dyn Picker>,
 }
 
 impl State {
-    pub fn new(storage: PathBuf, editor: String) -> Self {
+    pub fn new(store: CommandStore, editor: String) -> Self {
         State {
             editor,
-            storage,
-            tasks: Vec::new(),
+            store,
+            context: RunContext::default(),
+            lint_on_save: false,
+            default_template: None,
+            picker: Box::new(DialoguerPicker),
         }
     }
 
-    pub fn add(mut self, name: String) -> Result<()> {
-        self.load_tasks()?;
+    pub fn with_run_context(mut self, context: RunContext) -> Self {
+        self.context = context;
+        self
+    }
 
-        if self.tasks.contains(&name) {
-            anyhow::bail!("Command with this name already exists".red());
-        }
+    pub fn with_lint_on_save(mut self, lint_on_save: bool) -> Self {
+        self.lint_on_save = lint_on_save;
+        self
+    }
 
-        let temp_file = tempfile::NamedTempFile::new().unwrap();
-        let temp_path = temp_file.path().to_str().unwrap().to_string();
+    pub fn with_default_template(mut self, template: Option<String>) -> Self {
+        self.default_template = template;
+        self
+    }
 
-        let editor = &self.editor;
+    pub fn with_picker(mut self, picker: Box<dyn Picker>) -> Self {
+        self.picker = picker;
+        self
+    }
 
-        std::fs::write(&temp_path, " ").context("Failed to write to temp file".red())?;
+    pub fn store(&self) -> &CommandStore {
+        &self.store
+    }
 
-        let editor_status = std::process::Command::new(editor)
-            .arg(&temp_path)
-            .status()
-            .context("Failed to open editor".red())?;
+    pub fn context(&self) -> &RunContext {
+        &self.context
+    }
 
-        if !editor_status.success() {
-            anyhow::bail!("Editor exited with non-zero status".red());
-        }
+    pub fn add(&mut self, name: String, template: Option<String>) -> Result<()> {
+        self.store.check_available(&name, None)?;
+
+        let body = match template.or_else(|| self.default_template.clone()) {
+            Some(template) => template::load(self.store.root(), &template)?,
+            None => String::new(),
+        };
 
-        let command = std::fs::read_to_string(&temp_path)?;
-        std::fs::remove_file(&temp_path)?;
+        let draft = Draft::new(&name, &Metadata::default(), &body);
 
-        if !command.trim().is_empty() {
-            let file_path = self.get_file_path(name);
-            std::fs::write(&file_path, command).context("Failed to write command file".red())?;
-        }
+        let Some(draft) = self.edit_draft(draft, None)? else {
+            return Ok(());
+        };
+
+        let mut metadata = Metadata::default();
+        draft.apply_to(&mut metadata);
+        self.store.insert(&draft.name, &draft.body, metadata)?;
 
         Ok(())
     }
 
-    pub fn run(mut self, name: Option<String>) -> Result<()> {
-        if let None = name {
-            self.load_tasks()?;
-            if self.tasks.is_empty() {
-                println!("No commands found.");
-                println!("Use `zerp add <name>` to add a new command.");
-                return Ok(());
-            }
+    pub fn run(&mut self, name: Option<String>, options: &RunOptions) -> Result<()> {
+        if name.is_none() && self.store.is_empty() {
+            println!("No commands found.");
+            println!("Use `zerp add <name>` to add a new command.");
+            return Ok(());
         }
 
         let name = match name {
@@ -70,103 +97,272 @@ impl State {
 
         match name {
             Some(selected) => {
-                let file_path = self.get_file_path(selected);
+                let mut job = Job::prepare(&self.store, &selected, options, &self.context)?;
 
-                if !file_path.exists() {
-                    anyhow::bail!("Command not found");
+                if options.dry_run {
+                    job.print_dry_run();
+                    return Ok(());
                 }
 
-                let status = std::process::Command::new("sh")
-                    .arg(&file_path)
-                    .status()
-                    .context("Failed to execute command".red())?;
+                self.confirm_run(&job, options.yes)?;
+                job.resolve_secrets(&self.context)?;
 
-                if !status.success() {
-                    anyhow::bail!("Command exited with non-zero status".red());
+                let outcome = job.run()?;
+
+                if let Err(err) = self.context.record_run(&job.name, &outcome) {
+                    eprintln!("{} {:#}", "Failed to record run:".yellow(), err);
                 }
 
-                Ok(())
+                if outcome.success() {
+                    return Ok(());
+                }
+
+                let message = match outcome {
+                    Outcome::Exited(_) => {
+                        format!("Command exited with status {}", outcome.exit_code())
+                    }
+                    Outcome::TimedOut(timeout) => format!(
+                        "Command timed out after {}",
+                        humantime::format_duration(timeout)
+                    ),
+                };
+
+                Err(CommandFailed {
+                    message,
+                    code: outcome.exit_code(),
+                }
+                .into())
             }
-            None => return Ok(()),
+            None => Ok(()),
         }
     }
 
-    pub fn delete(mut self, name: Option<String>) -> Result<()> {
-        if let None = name {
-            self.load_tasks()?;
-            if self.tasks.is_empty() {
+    pub fn run_parallel(
+        &mut self,
+        names: Vec<String>,
+        jobs: Option<usize>,
+        options: &RunOptions,
+    ) -> Result<()> {
+        let names = if names.is_empty() {
+            if self.store.is_empty() {
                 println!("No commands found.");
+                println!("Use `zerp add <name>` to add a new command.");
+                return Ok(());
+            }
+
+            let selected = self.select_commands("Select commands to run")?;
+
+            if selected.len() > 1 && !options.yes && !confirm_selection("run", &selected)? {
                 return Ok(());
             }
+
+            selected
+        } else {
+            names
+        };
+
+        if names.is_empty() {
+            return Ok(());
         }
 
-        let name = match name {
-            Some(n) => Some(n),
-            None => self.select_command("Select a command to delete")?,
+        if jobs == Some(0) {
+            anyhow::bail!("--jobs must be at least 1".red());
+        }
+
+        let mut scheduled = Vec::with_capacity(names.len());
+
+        for name in names {
+            scheduled.push(Job::prepare(&self.store, &name, options, &self.context)?);
+        }
+
+        if options.dry_run {
+            for job in &scheduled {
+                job.print_dry_run();
+            }
+            return Ok(());
+        }
+
+        for job in &scheduled {
+            self.confirm_run(job, options.yes)?;
+        }
+
+        for job in &mut scheduled {
+            job.resolve_secrets(&self.context)?;
+        }
+
+        runner::run_parallel(scheduled, jobs, &self.context)
+    }
+
+    pub fn delete(&mut self, names: Vec<String>) -> Result<()> {
+        let names = if names.is_empty() {
+            if self.store.is_empty() {
+                println!("No commands found.");
+                return Ok(());
+            }
+
+            self.select_commands("Select commands to delete")?
+        } else {
+            names
         };
 
-        match name {
-            Some(selected) => {
-                let file_path = self.get_file_path(selected.clone());
+        if names.is_empty() {
+            return Ok(());
+        }
 
-                if !file_path.exists() {
-                    anyhow::bail!("Command not found".red());
-                }
+        for name in &names {
+            self.store.require(name)?;
+        }
 
-                let mut confirm = String::new();
-                print!(
-                    "Are you sure you want to delete {} command? (y/N): ",
-                    selected.green()
-                );
+        let confirmed = match names.as_slice() {
+            [name] => confirm(&format!(
+                "Are you sure you want to delete {} command?",
+                name.green()
+            ))?,
+            _ => confirm_selection("delete", &names)?,
+        };
+
+        if !confirmed {
+            return Ok(());
+        }
 
-                std::io::stdout()
-                    .flush()
-                    .context("Failed to flush stdout")?;
+        for name in &names {
+            self.store.remove(name)?;
+            self.context.forget_runs(name)?;
+        }
 
-                std::io::stdin()
-                    .read_line(&mut confirm)
-                    .context("Failed to read input")?;
+        Ok(())
+    }
 
-                confirm = confirm.trim().to_string();
+    /// Move a command to the trash, returning where it went
+    pub fn trash(&mut self, name: &str) -> Result<PathBuf> {
+        let path = self.store.trash(name)?;
+        self.context.forget_runs(name)?;
+        Ok(path)
+    }
 
-                match confirm.as_str() {
-                    "y" | "Y" => {
-                        std::fs::remove_file(&file_path)
-                            .context(format!("Failed to delete {} command", selected))?;
-                    }
-                    _ => {}
-                }
+    pub fn tag(&mut self, tag: String, names: Vec<String>, remove: bool) -> Result<()> {
+        if tag.trim().is_empty() || tag.contains(',') {
+            anyhow::bail!("Tags cannot be empty or contain commas".red());
+        }
 
-                Ok(())
+        let names = if names.is_empty() {
+            if self.store.is_empty() {
+                println!("No commands found.");
+                return Ok(());
+            }
+
+            let selected = self.select_commands("Select commands to tag")?;
+            let action = if remove {
+                format!("remove tag '{}' from", tag)
+            } else {
+                format!("add tag '{}' to", tag)
+            };
+
+            if selected.len() > 1 && !confirm_selection(&action, &selected)? {
+                return Ok(());
+            }
+
+            selected
+        } else {
+            names
+        };
+
+        for name in &names {
+            let mut metadata = self.store.require(name)?.metadata()?.clone();
+
+            if remove {
+                metadata.tags.retain(|existing| *existing != tag);
+            } else if !metadata.tags.contains(&tag) {
+                metadata.tags.push(tag.clone());
             }
-            None => return Ok(()),
+
+            self.store.set_metadata(name, metadata)?;
         }
+
+        Ok(())
     }
 
-    pub fn list(mut self) -> Result<()> {
-        self.load_tasks()?;
+    pub fn list(&mut self) -> Result<()> {
+        if self.store.is_empty() {
+            println!("No commands found.");
+            println!("Use `zerp add <name>` to add a new command.");
+            return Ok(());
+        }
+
+        for entry in self.store.iter() {
+            println!("{}", entry.name());
+        }
 
-        if self.tasks.is_empty() {
+        Ok(())
+    }
+
+    pub fn show(&mut self, name: Option<String>) -> Result<()> {
+        if name.is_none() && self.store.is_empty() {
             println!("No commands found.");
             println!("Use `zerp add <name>` to add a new command.");
             return Ok(());
         }
 
-        for task in &self.tasks {
-            println!("{}", task);
+        let name = match name {
+            Some(n) => n,
+            None => match self.select_command("Select a command to show")? {
+                Some(selected) => selected,
+                None => return Ok(()),
+            },
+        };
+
+        let entry = self.store.require(&name)?;
+        let metadata = entry.metadata()?;
+
+        println!("{}", entry.name().bold());
+
+        if let Some(description) = &metadata.description {
+            println!("{}", description);
+        }
+
+        if !metadata.tags.is_empty() {
+            println!("{} {}", "tags:".dimmed(), metadata.tags.join(", "));
+        }
+
+        if let Some(history) = &self.context.history
+            && let Some(last_run) = history.last_run(&name)?
+        {
+            println!(
+                "{} {} (exit {})",
+                "last run:".dimmed(),
+                last_run.ago(),
+                last_run.exit_code
+            );
+        }
+
+        println!();
+        print!("{}", entry.script());
+
+        if !entry.script().ends_with('\n') {
+            println!();
         }
 
         Ok(())
     }
 
-    pub fn edit(mut self, name: Option<String>) -> Result<()> {
-        if let None = name {
-            self.load_tasks()?;
-            if self.tasks.is_empty() {
-                println!("No commands found.");
-                println!("Use `zerp add <name>` to add a new command.");
-                return Ok(());
-            }
+    /// Pick a command and act on it with the key that accepted the pick,
+    /// running it on enter
+    pub fn browse(&mut self) -> Result<()> {
+        if self.store.is_empty() {
+            println!("No commands found.");
+            println!("Use `zerp add <name>` to add a new command.");
+            return Ok(());
+        }
+
+        let selection = self.picker.browse(&self.store, "Select a command")?;
+        self.perform(selection.action.unwrap_or(Action::Run), selection.names)
+    }
+
+    pub fn edit(&mut self, name: Option<String>, meta: bool) -> Result<()> {
+        if name.is_none() && self.store.is_empty() {
+            println!("No commands found.");
+            println!("Use `zerp add <name>` to add a new command.");
+            return Ok(());
         }
 
         let name = match name {
@@ -176,30 +372,116 @@ impl State {
 
         match name {
             Some(selected) => {
-                let file_path = self.get_file_path(selected);
+                self.store.require(&selected)?;
 
-                if !file_path.exists() {
-                    anyhow::bail!("Task not found");
+                if meta {
+                    return self.edit_metadata(&selected);
                 }
 
-                let editor = &self.editor;
+                self.edit_command(&selected)
+            }
+            None => Ok(()),
+        }
+    }
+
+    pub fn lint(&mut self, name: Option<String>, all: bool) -> Result<()> {
+        let names = if all {
+            self.store.names()
+        } else {
+            if name.is_none() && self.store.is_empty() {
+                println!("No commands found.");
+                return Ok(());
+            }
+
+            match name {
+                Some(n) => vec![n],
+                None => match self.select_command("Select a command to lint")? {
+                    Some(selected) => vec![selected],
+                    None => return Ok(()),
+                },
+            }
+        };
+
+        if !lint::is_shellcheck_available() {
+            println!(
+                "{}",
+                "shellcheck not found, using the built-in checks".dimmed()
+            );
+        }
 
-                let editor_status = std::process::Command::new(editor)
-                    .arg(&file_path)
-                    .status()
-                    .context("Failed to open editor")?;
+        let mut failed = 0;
 
-                if !editor_status.success() {
-                    anyhow::bail!("Editor exited with non-zero status");
-                }
+        for name in &names {
+            let entry = self.store.require(name)?;
+            let diagnostics = match entry.path() {
+                Some(path) => lint::lint(path)?,
+                None => lint::lint_script(entry.script())?,
+            };
+            lint::print_diagnostics(name, &diagnostics);
 
-                Ok(())
+            if lint::has_errors(&diagnostics) {
+                failed += 1;
             }
-            None => return Ok(()),
         }
+
+        if failed > 0 {
+            anyhow::bail!(format!("{} of {} commands have errors", failed, names.len()).red());
+        }
+
+        println!(
+            "{}",
+            format!("No errors in {} command(s)", names.len()).green()
+        );
+        Ok(())
     }
 
-    pub fn rename(mut self, current_name: String, new_name: String) -> Result<()> {
+    pub fn rename(&mut self, current_name: String, new_name: String) -> Result<()> {
+        self.check_new_name(&current_name, &new_name)?;
+        self.store.rename(&current_name, &new_name)?;
+        self.context.rename_runs(&current_name, &new_name)
+    }
+
+    pub fn copy(
+        &mut self,
+        source: Option<String>,
+        destination: Option<String>,
+        edit: bool,
+    ) -> Result<()> {
+        if source.is_none() && self.store.is_empty() {
+            println!("No commands found.");
+            println!("Use `zerp add <name>` to add a new command.");
+            return Ok(());
+        }
+
+        let source = match source {
+            Some(n) => n,
+            None => match self.select_command("Select a command to copy")? {
+                Some(selected) => selected,
+                None => return Ok(()),
+            },
+        };
+
+        let destination = match destination {
+            Some(n) => n,
+            None => dialoguer::Input::<String>::new()
+                .with_prompt(format!("Copy {} to", source))
+                .interact_text()?,
+        };
+
+        self.check_new_name(&source, &destination)?;
+        self.store.copy(&source, &destination)?;
+
+        println!("Copied {} to {}", source.green(), destination.green());
+
+        if edit {
+            self.edit_command(&destination)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks shared by every operation giving an existing command a new name
+    fn check_new_name(&self, current_name: &str, new_name: &str) -> Result<()> {
         if new_name.is_empty() {
             anyhow::bail!("New name cannot be empty".red());
         }
@@ -207,71 +489,330 @@ impl State {
             anyhow::bail!("Current name and new name cannot be the same".red());
         }
 
-        self.load_tasks()?;
+        self.store.require(current_name)?;
+        self.store.check_available(new_name, Some(current_name))
+    }
 
-        if self.tasks.contains(&new_name) {
-            anyhow::bail!(format!("Command '{}' already exists", new_name).red());
-        }
+    /// Edit an existing command's script and header, saving the result
+    fn edit_command(&mut self, name: &str) -> Result<()> {
+        let entry = self.store.require(name)?;
+        let mut metadata = entry.metadata()?.clone();
+        let body = entry.script().to_string();
 
-        let current_file_path = self.get_file_path(current_name.clone());
-        let new_file_path = self.get_file_path(new_name.clone());
+        let draft = Draft::new(name, &metadata, &body);
 
-        if !current_file_path.exists() {
-            anyhow::bail!("Command not found");
+        let Some(draft) = self.edit_draft(draft, Some(name))? else {
+            return Ok(());
+        };
+
+        draft.apply_to(&mut metadata);
+        self.store.insert(name, &draft.body, metadata)?;
+
+        if draft.name != name {
+            self.rename(name.to_string(), draft.name)?;
         }
 
-        if new_file_path.exists() {
-            anyhow::bail!("Command with this name already exists");
+        Ok(())
+    }
+
+    /// Edit a command's metadata file in place, or as a TOML buffer for
+    /// backends without metadata files
+    fn edit_metadata(&mut self, name: &str) -> Result<()> {
+        if let Some(path) = self.store.metadata_path(name)? {
+            self.open_editor(&path)?;
+            return self.store.refresh();
         }
 
-        std::fs::rename(&current_file_path, &new_file_path)
-            .context("Failed to rename command file")?;
+        let metadata = self.store.require(name)?.metadata()?.clone();
+        let file = tempfile::Builder::new()
+            .prefix("zerp-")
+            .suffix(".toml")
+            .tempfile()
+            .context("Failed to create temp file".red())?;
+
+        let content = toml::to_string(&metadata).context("Failed to serialize metadata")?;
+        std::fs::write(file.path(), content).context("Failed to write to temp file".red())?;
 
+        self.open_editor(file.path())?;
+
+        let content = std::fs::read_to_string(file.path()).context("Failed to read temp file")?;
+        let metadata: Metadata = toml::from_str(&content)
+            .map_err(|err| anyhow::anyhow!(format!("Invalid metadata: {}", err).red()))?;
+
+        self.store.set_metadata(name, metadata)?;
         Ok(())
     }
 
-    fn get_file_path(&self, name: String) -> PathBuf {
-        self.storage.join(format!("{}.sh", name))
+    fn open_editor(&self, path: &Path) -> Result<()> {
+        let editor_status = std::process::Command::new(&self.editor)
+            .arg(path)
+            .status()
+            .context("Failed to open editor".red())?;
+
+        if !editor_status.success() {
+            anyhow::bail!("Editor exited with non-zero status".red());
+        }
+
+        Ok(())
+    }
+
+    /// Edit a draft in a temp buffer until it can be saved.
+    ///
+    /// When the editor fails, the script is empty, linting on save finds errors
+    /// or the name in the header is taken, the user chooses between editing
+    /// again, saving anyway and discarding. Returns `None` when discarded.
+    fn edit_draft(&self, draft: Draft, current_name: Option<&str>) -> Result<Option<Draft>> {
+        let file = tempfile::Builder::new()
+            .prefix("zerp-")
+            .suffix(".sh")
+            .tempfile()
+            .context("Failed to create temp file".red())?;
+
+        std::fs::write(file.path(), draft.render())
+            .context("Failed to write to temp file".red())?;
+
+        loop {
+            let editor_ok = std::process::Command::new(&self.editor)
+                .arg(file.path())
+                .status()
+                .context("Failed to open editor".red())?
+                .success();
+
+            let buffer =
+                std::fs::read_to_string(file.path()).context("Failed to read temp file")?;
+            let edited = draft.parse(&buffer);
+
+            let Some((problem, can_save)) = self.draft_problem(&edited, current_name, editor_ok)?
+            else {
+                return Ok(Some(edited));
+            };
+
+            match prompt_recovery(&problem, can_save)? {
+                Some(Recovery::EditAgain) => continue,
+                Some(Recovery::SaveAnyway) => return Ok(Some(edited)),
+                Some(Recovery::Discard) => return Ok(None),
+                None => {
+                    let (_, path) = file.keep().context("Failed to keep temp file")?;
+                    anyhow::bail!(format!("{}, draft kept at {}", problem, path.display()).red());
+                }
+            }
+        }
     }
 
-    fn load_tasks(&mut self) -> anyhow::Result<()> {
-        let entries =
-            std::fs::read_dir(&self.storage).context("Failed to read storage directory".red())?;
+    /// Why an edited draft can't be saved as is, and whether saving it anyway is allowed
+    fn draft_problem(
+        &self,
+        draft: &Draft,
+        current_name: Option<&str>,
+        editor_ok: bool,
+    ) -> Result<Option<(String, bool)>> {
+        if let Err(err) = self.store.check_available(&draft.name, current_name) {
+            return Ok(Some((err.to_string(), false)));
+        }
+
+        if !editor_ok {
+            return Ok(Some((
+                "Editor exited with non-zero status".to_string(),
+                true,
+            )));
+        }
 
-        for entry in entries {
-            let entry = entry.context("Failed to read entry".red())?;
-            let path = entry.path();
+        if draft.body.trim().is_empty() {
+            return Ok(Some(("The command is empty".to_string(), true)));
+        }
 
-            if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("sh") {
-                let name = path
-                    .file_stem()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or_default()
-                    .to_string();
+        if self.lint_on_save {
+            let diagnostics = lint::lint_script(&draft.body)?;
+            lint::print_diagnostics(&draft.name, &diagnostics);
 
-                self.tasks.push(name);
+            if lint::has_errors(&diagnostics) {
+                return Ok(Some(("The command has errors".to_string(), true)));
             }
         }
 
+        Ok(None)
+    }
+
+    /// Show a dangerous command and require its name to be typed before it runs
+    fn confirm_run(&self, job: &Job, yes: bool) -> Result<()> {
+        let Some(reason) = &job.confirm else {
+            return Ok(());
+        };
+
+        if yes {
+            return Ok(());
+        }
+
+        if !std::io::stdin().is_terminal() {
+            anyhow::bail!(
+                format!(
+                    "Command '{}' requires confirmation ({}), pass --yes to run it",
+                    job.name, reason
+                )
+                .red()
+            );
+        }
+
+        // Shown as it runs, except for the secrets, which are only resolved
+        // once confirmed
+        let script = secret::mask(&job.script);
+
+        println!(
+            "{} {} {}",
+            "Warning:".yellow().bold(),
+            job.name.green(),
+            reason
+        );
+        if let Some(cwd) = &job.cwd {
+            println!("{}", format!("Runs in {}", cwd.display()).dimmed());
+        }
+        println!("{}", "─".repeat(40).dimmed());
+        print!("{}", script);
+        if !script.ends_with('\n') {
+            println!();
+        }
+        println!("{}", "─".repeat(40).dimmed());
+
+        print!("Type {} to run it: ", job.name.green());
+        std::io::stdout()
+            .flush()
+            .context("Failed to flush stdout")?;
+
+        let mut answer = String::new();
+        std::io::stdin()
+            .read_line(&mut answer)
+            .context("Failed to read input")?;
+
+        if answer.trim() != job.name {
+            anyhow::bail!("Aborted".red());
+        }
+
         Ok(())
     }
 
-    fn select_command(&mut self, header: &str) -> Result<Option<String>> {
-        if crate::fzf::is_fzf_available() {
-            return crate::fzf::select_task_with_preview(&self.storage, header);
+    /// Pick any number of commands, returning them in list order. A pick
+    /// accepted with a key bound to an action performs that action instead and
+    /// returns nothing.
+    fn select_commands(&mut self, header: &str) -> Result<Vec<String>> {
+        let selection = self.picker.pick_action(&self.store, header, true)?;
+
+        match selection.action {
+            Some(action) => {
+                self.perform(action, selection.names)?;
+                Ok(Vec::new())
+            }
+            None => Ok(selection.names),
         }
+    }
 
-        use dialoguer::{Select, theme::ColorfulTheme};
+    fn select_command(&mut self, header: &str) -> Result<Option<String>> {
+        let selection = self.picker.pick_action(&self.store, header, false)?;
 
-        let selection = Select::with_theme(&ColorfulTheme::default())
-            .with_prompt(header)
-            .default(0)
-            .items(&self.tasks)
-            .interact_opt()?;
+        match selection.action {
+            Some(action) => {
+                self.perform(action, selection.names)?;
+                Ok(None)
+            }
+            None => Ok(selection.names.into_iter().next()),
+        }
+    }
 
-        match selection {
-            Some(index) => Ok(Some(self.tasks[index].clone())),
-            None => Ok(None),
+    /// Act on commands picked with a bound key, with default options
+    fn perform(&mut self, action: Action, names: Vec<String>) -> Result<()> {
+        if names.is_empty() {
+            return Ok(());
+        }
+
+        match action {
+            Action::Run => match names.as_slice() {
+                [name] => self.run(Some(name.clone()), &RunOptions::default()),
+                _ => self.run_parallel(names, None, &RunOptions::default()),
+            },
+            Action::Edit => {
+                for name in names {
+                    self.edit(Some(name), false)?;
+                }
+                Ok(())
+            }
+            Action::Show => {
+                for name in names {
+                    self.show(Some(name))?;
+                }
+                Ok(())
+            }
+            Action::Copy => {
+                for name in names {
+                    self.copy(Some(name), None, false)?;
+                }
+                Ok(())
+            }
+            Action::Delete => self.delete(names),
+            Action::Rename => {
+                for name in names {
+                    let new_name = dialoguer::Input::<String>::new()
+                        .with_prompt(format!("Rename {} to", name))
+                        .interact_text()?;
+
+                    self.rename(name, new_name)?;
+                }
+                Ok(())
+            }
         }
     }
 }
+
+enum Recovery {
+    EditAgain,
+    SaveAnyway,
+    Discard,
+}
+
+/// Ask what to do with a draft that can't be saved as is. Returns `None` when
+/// there is no one to ask or the prompt is cancelled.
+fn prompt_recovery(problem: &str, can_save: bool) -> Result<Option<Recovery>> {
+    if !std::io::stdin().is_terminal() {
+        return Ok(None);
+    }
+
+    let mut choices = vec![("Edit again", Recovery::EditAgain)];
+    if can_save {
+        choices.push(("Save anyway", Recovery::SaveAnyway));
+    }
+    choices.push(("Discard", Recovery::Discard));
+
+    let labels: Vec<_> = choices.iter().map(|(label, _)| *label).collect();
+
+    let selection = dialoguer::Select::with_theme(&dialoguer::theme::ColorfulTheme::default())
+        .with_prompt(problem)
+        .default(0)
+        .items(&labels)
+        .interact_opt()?;
+
+    Ok(selection.map(|index| choices.swap_remove(index).1))
+}
+
+/// Ask a yes/no question, defaulting to no
+fn confirm(prompt: &str) -> Result<bool> {
+    let mut answer = String::new();
+    print!("{} (y/N): ", prompt);
+
+    std::io::stdout()
+        .flush()
+        .context("Failed to flush stdout")?;
+
+    std::io::stdin()
+        .read_line(&mut answer)
+        .context("Failed to read input")?;
+
+    Ok(matches!(answer.trim(), "y" | "Y"))
+}
+
+/// Confirm an action on several commands at once, listing all of them
+fn confirm_selection(action: &str, names: &[String]) -> Result<bool> {
+    println!("The following {} commands were selected:", names.len());
+    for name in names {
+        println!("  {}", name.green());
+    }
+
+    confirm(&format!("Are you sure you want to {} them?", action))
+}
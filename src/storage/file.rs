@@ -0,0 +1,199 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::{Listing, Storage, modified_time};
+use crate::metadata::Metadata;
+
+/// Serialization format of a single-file store
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    Toml,
+    Json,
+}
+
+impl FileFormat {
+    fn file_name(self) -> &'static str {
+        match self {
+            FileFormat::Toml => "commands.toml",
+            FileFormat::Json => "commands.json",
+        }
+    }
+}
+
+/// A command as written in the single file: its script next to the metadata fields
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct StoredCommand {
+    script: String,
+    #[serde(flatten)]
+    metadata: Metadata,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Document {
+    #[serde(default)]
+    commands: BTreeMap<String, StoredCommand>,
+}
+
+/// Every command in one TOML or JSON file in the storage directory, which is
+/// easy to sync or keep in version control.
+pub struct FileStorage {
+    path: PathBuf,
+    format: FileFormat,
+    /// Parsed document, with the modification time of the file it was read from
+    cache: RefCell<Option<(Option<SystemTime>, Document)>>,
+}
+
+impl FileStorage {
+    pub fn new(root: &Path, format: FileFormat) -> Self {
+        FileStorage {
+            path: root.join(format.file_name()),
+            format,
+            cache: RefCell::new(None),
+        }
+    }
+
+    /// Run `f` on the document, re-reading the file only when it changed
+    fn with_document<T>(&self, f: impl FnOnce(&Document) -> Result<T>) -> Result<T> {
+        let modified = modified_time(&self.path);
+        let mut cache = self.cache.borrow_mut();
+
+        if cache.as_ref().is_none_or(|(cached, _)| *cached != modified) {
+            *cache = Some((modified, self.read()?));
+        }
+
+        let (_, document) = cache.as_ref().context("Command file is not loaded")?;
+        f(document)
+    }
+
+    fn command(&self, name: &str) -> Result<StoredCommand> {
+        self.with_document(|document| {
+            document
+                .commands
+                .get(name)
+                .cloned()
+                .with_context(|| format!("Command '{}' not found", name))
+        })
+    }
+
+    /// Apply `f` to the document and write it back
+    fn update(&mut self, f: impl FnOnce(&mut Document) -> Result<()>) -> Result<()> {
+        let mut document = self.with_document(|document| Ok(document.clone()))?;
+        f(&mut document)?;
+        self.write(&document)?;
+
+        *self.cache.borrow_mut() = Some((modified_time(&self.path), document));
+        Ok(())
+    }
+
+    fn read(&self) -> Result<Document> {
+        if !self.path.exists() {
+            return Ok(Document::default());
+        }
+
+        let content = fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read {}", self.path.display()))?;
+
+        match self.format {
+            FileFormat::Toml => toml::from_str(&content).map_err(anyhow::Error::from),
+            FileFormat::Json => serde_json::from_str(&content).map_err(anyhow::Error::from),
+        }
+        .with_context(|| format!("Failed to parse {}", self.path.display()))
+    }
+
+    fn write(&self, document: &Document) -> Result<()> {
+        let content = match self.format {
+            FileFormat::Toml => toml::to_string(document).map_err(anyhow::Error::from),
+            FileFormat::Json => serde_json::to_string_pretty(document).map_err(anyhow::Error::from),
+        }
+        .context("Failed to serialize commands")?;
+
+        // Write to a temp file first, so a failure never leaves a truncated store
+        let dir = self.path.parent().context("Invalid command file path")?;
+        let mut file =
+            tempfile::NamedTempFile::new_in(dir).context("Failed to create temp file")?;
+        std::io::Write::write_all(&mut file, content.as_bytes())
+            .context("Failed to write commands")?;
+        file.persist(&self.path)
+            .with_context(|| format!("Failed to write {}", self.path.display()))?;
+
+        Ok(())
+    }
+}
+
+impl Storage for FileStorage {
+    fn location(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn list(&self) -> Result<Vec<Listing>> {
+        let modified = modified_time(&self.path);
+
+        self.with_document(|document| {
+            Ok(document
+                .commands
+                .keys()
+                .map(|name| Listing {
+                    name: name.clone(),
+                    modified,
+                    metadata_modified: None,
+                })
+                .collect())
+        })
+    }
+
+    fn read_script(&self, name: &str) -> Result<String> {
+        Ok(self.command(name)?.script)
+    }
+
+    fn read_metadata(&self, name: &str) -> Result<Metadata> {
+        Ok(self.command(name)?.metadata)
+    }
+
+    fn write_script(&mut self, name: &str, script: &str) -> Result<()> {
+        self.update(|document| {
+            document
+                .commands
+                .entry(name.to_string())
+                .or_default()
+                .script = script.to_string();
+            Ok(())
+        })
+    }
+
+    fn write_metadata(&mut self, name: &str, metadata: &Metadata) -> Result<()> {
+        self.update(|document| {
+            document
+                .commands
+                .get_mut(name)
+                .with_context(|| format!("Command '{}' not found", name))?
+                .metadata = metadata.clone();
+            Ok(())
+        })
+    }
+
+    fn remove(&mut self, name: &str) -> Result<()> {
+        self.update(|document| {
+            document
+                .commands
+                .remove(name)
+                .with_context(|| format!("Command '{}' not found", name))?;
+            Ok(())
+        })
+    }
+
+    fn rename(&mut self, current_name: &str, new_name: &str) -> Result<()> {
+        self.update(|document| {
+            let command = document
+                .commands
+                .remove(current_name)
+                .with_context(|| format!("Command '{}' not found", current_name))?;
+            document.commands.insert(new_name.to_string(), command);
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,138 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::PathBuf;
+
+use super::{Listing, Storage, modified_time};
+use crate::metadata::{self, Metadata};
+use crate::naming;
+
+/// Extension of the script file holding each command
+const SCRIPT_EXTENSION: &str = "sh";
+
+/// The original layout: `<name>.sh` files in the storage directory, with the
+/// metadata in an optional `<name>.meta.toml` next to each one.
+pub struct FsStorage {
+    root: PathBuf,
+}
+
+impl FsStorage {
+    pub fn new(root: PathBuf) -> Self {
+        FsStorage { root }
+    }
+
+    fn script_file(&self, name: &str) -> PathBuf {
+        self.root.join(format!("{}.{}", name, SCRIPT_EXTENSION))
+    }
+
+    fn metadata_file(&self, name: &str) -> PathBuf {
+        self.root
+            .join(format!("{}{}", name, metadata::METADATA_SUFFIX))
+    }
+}
+
+impl Storage for FsStorage {
+    fn location(&self) -> String {
+        self.root.display().to_string()
+    }
+
+    fn list(&self) -> Result<Vec<Listing>> {
+        let dir = fs::read_dir(&self.root).context("Failed to read storage directory")?;
+        let mut listings = Vec::new();
+
+        for entry in dir {
+            let path = entry.context("Failed to read entry")?.path();
+
+            if !path.is_file()
+                || path.extension().and_then(|s| s.to_str()) != Some(SCRIPT_EXTENSION)
+            {
+                continue;
+            }
+
+            let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+
+            // A command changes when either of its files does, including
+            // when its metadata file is deleted
+            let metadata_modified = modified_time(&self.metadata_file(name));
+            let modified = modified_time(&path).max(metadata_modified);
+
+            listings.push(Listing {
+                name: name.to_string(),
+                modified,
+                metadata_modified,
+            });
+        }
+
+        Ok(listings)
+    }
+
+    fn read_script(&self, name: &str) -> Result<String> {
+        fs::read_to_string(self.script_file(name))
+            .with_context(|| format!("Failed to read command file for '{}'", name))
+    }
+
+    fn read_metadata(&self, name: &str) -> Result<Metadata> {
+        Metadata::load(&self.metadata_file(name))
+    }
+
+    fn write_script(&mut self, name: &str, script: &str) -> Result<()> {
+        fs::write(self.script_file(name), script).context("Failed to write command file")
+    }
+
+    fn write_metadata(&mut self, name: &str, metadata: &Metadata) -> Result<()> {
+        metadata.save(&self.metadata_file(name))
+    }
+
+    fn remove(&mut self, name: &str) -> Result<()> {
+        fs::remove_file(self.script_file(name))
+            .context(format!("Failed to delete {} command", name))?;
+
+        let metadata_path = self.metadata_file(name);
+        if metadata_path.exists() {
+            fs::remove_file(&metadata_path).context("Failed to delete command metadata")?;
+        }
+
+        Ok(())
+    }
+
+    /// Move the files rather than rewriting them, so hand-written formatting
+    /// and comments in the metadata survive
+    fn rename(&mut self, current_name: &str, new_name: &str) -> Result<()> {
+        fs::rename(self.script_file(current_name), self.script_file(new_name))
+            .context("Failed to rename command file")?;
+
+        let current_metadata_path = self.metadata_file(current_name);
+        if current_metadata_path.exists() {
+            fs::rename(&current_metadata_path, self.metadata_file(new_name))
+                .context("Failed to rename command metadata")?;
+        }
+
+        Ok(())
+    }
+
+    fn copy(&mut self, source: &str, destination: &str) -> Result<()> {
+        fs::copy(self.script_file(source), self.script_file(destination))
+            .context("Failed to copy command file")?;
+
+        let source_metadata_path = self.metadata_file(source);
+        if source_metadata_path.exists() {
+            fs::copy(&source_metadata_path, self.metadata_file(destination))
+                .context("Failed to copy command metadata")?;
+        }
+
+        Ok(())
+    }
+
+    fn script_path(&self, name: &str) -> Option<PathBuf> {
+        Some(self.script_file(name))
+    }
+
+    fn metadata_path(&self, name: &str) -> Option<PathBuf> {
+        Some(self.metadata_file(name))
+    }
+
+    fn is_case_insensitive(&self) -> bool {
+        naming::is_case_insensitive(&self.root)
+    }
+}
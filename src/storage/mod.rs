@@ -0,0 +1,336 @@
+mod file;
+mod fs;
+mod multi;
+mod sqlite;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use crate::metadata::Metadata;
+
+pub use file::{FileFormat, FileStorage};
+pub use fs::FsStorage;
+pub use multi::{MultiStorage, PROFILE_SEPARATOR};
+pub use sqlite::SqliteStorage;
+
+/// A command name with the last time it changed, as listed by a backend
+#[derive(Debug, Clone)]
+pub struct Listing {
+    pub name: String,
+    pub modified: Option<SystemTime>,
+    /// Last time the command's own metadata file changed, for backends
+    /// keeping one; `None` when it has none
+    pub metadata_modified: Option<SystemTime>,
+}
+
+/// Where and how commands are persisted.
+///
+/// Backends only deal with raw reads and writes; name validation, indexing and
+/// collision checks live in `CommandStore`.
+pub trait Storage {
+    /// Short description of where commands are kept, for messages
+    fn location(&self) -> String;
+
+    /// All stored commands, in any order
+    fn list(&self) -> Result<Vec<Listing>>;
+
+    fn read_script(&self, name: &str) -> Result<String>;
+
+    fn read_metadata(&self, name: &str) -> Result<Metadata>;
+
+    /// Create the command or replace its script
+    fn write_script(&mut self, name: &str, script: &str) -> Result<()>;
+
+    /// Replace the metadata of an existing command
+    fn write_metadata(&mut self, name: &str, metadata: &Metadata) -> Result<()>;
+
+    fn remove(&mut self, name: &str) -> Result<()>;
+
+    fn rename(&mut self, current_name: &str, new_name: &str) -> Result<()> {
+        let script = self.read_script(current_name)?;
+        let metadata = self.read_metadata(current_name)?;
+
+        self.write_script(new_name, &script)?;
+        self.write_metadata(new_name, &metadata)?;
+        self.remove(current_name)
+    }
+
+    fn copy(&mut self, source: &str, destination: &str) -> Result<()> {
+        let script = self.read_script(source)?;
+        let metadata = self.read_metadata(source)?;
+
+        self.write_script(destination, &script)?;
+        self.write_metadata(destination, &metadata)
+    }
+
+    /// Name of the command in the storage actually keeping it, which differs
+    /// from `name` for storages combining several others
+    fn local_name<'a>(&self, name: &'a str) -> Result<&'a str> {
+        Ok(name)
+    }
+
+    /// Script file that can be executed in place, for backends keeping one
+    /// file per command
+    fn script_path(&self, _name: &str) -> Option<PathBuf> {
+        None
+    }
+
+    /// Metadata file that can be edited in place, for backends keeping one
+    /// file per command
+    fn metadata_path(&self, _name: &str) -> Option<PathBuf> {
+        None
+    }
+
+    /// Whether names differing only by case refer to the same command
+    fn is_case_insensitive(&self) -> bool {
+        false
+    }
+}
+
+/// The available storage backends, selected with `storage_backend` in `config.toml`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// One `.sh` file per command, with an optional `.meta.toml` next to it
+    #[default]
+    Fs,
+    /// Every command in a single `commands.toml`
+    Toml,
+    /// Every command in a single `commands.json`
+    Json,
+    /// Every command in a `commands.db` SQLite database
+    Sqlite,
+}
+
+impl Backend {
+    pub const ALL: [Backend; 4] = [Backend::Fs, Backend::Toml, Backend::Json, Backend::Sqlite];
+
+    /// Open this backend on the storage directory `root`
+    pub fn open(self, root: &Path) -> Result<Box<dyn Storage>> {
+        Ok(match self {
+            Backend::Fs => Box::new(FsStorage::new(root.to_path_buf())),
+            Backend::Toml => Box::new(FileStorage::new(root, FileFormat::Toml)),
+            Backend::Json => Box::new(FileStorage::new(root, FileFormat::Json)),
+            Backend::Sqlite => Box::new(SqliteStorage::open(root)?),
+        })
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Backend::Fs => "fs",
+            Backend::Toml => "toml",
+            Backend::Json => "json",
+            Backend::Sqlite => "sqlite",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Backend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Backend::ALL
+            .into_iter()
+            .find(|backend| backend.to_string() == s.to_lowercase())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown storage backend '{}', expected one of: fs, toml, json, sqlite",
+                    s
+                )
+            })
+    }
+}
+
+/// Copy every command from `source` into `target`, which must be empty, and
+/// check that each one reads back identically. Returns the number of commands.
+pub fn migrate(source: &dyn Storage, target: &mut dyn Storage) -> Result<usize> {
+    if !target.list()?.is_empty() {
+        anyhow::bail!("{} already contains commands", target.location());
+    }
+
+    let mut listings = source.list()?;
+    listings.sort_by(|a, b| a.name.cmp(&b.name));
+
+    for listing in &listings {
+        let name = &listing.name;
+        let script = source.read_script(name)?;
+        let metadata = source
+            .read_metadata(name)
+            .with_context(|| format!("Failed to read metadata of '{}'", name))?;
+
+        target.write_script(name, &script)?;
+        target.write_metadata(name, &metadata)?;
+
+        if target.read_script(name)? != script || target.read_metadata(name)? != metadata {
+            anyhow::bail!(
+                "'{}' changed while being copied to {}",
+                name,
+                target.location()
+            );
+        }
+    }
+
+    Ok(listings.len())
+}
+
+/// Delete every command from `storage`, as after migrating them elsewhere
+pub fn remove_all(storage: &mut dyn Storage) -> Result<()> {
+    for listing in storage.list()? {
+        storage.remove(&listing.name)?;
+    }
+
+    Ok(())
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn commands() -> Vec<(&'static str, &'static str, Metadata)> {
+        let full = Metadata {
+            description: Some("Deploy to \"prod\"".to_string()),
+            tags: vec!["ops".to_string(), "prod".to_string()],
+            cwd: Some("@project".to_string()),
+            env_file: Some(".env.prod".to_string()),
+            timeout: Some("5m".to_string()),
+            retries: Some(3),
+            retry_delay: Some("10s".to_string()),
+            backoff: true,
+            confirm: true,
+            env: BTreeMap::from([
+                ("REGION".to_string(), "eu-west-1".to_string()),
+                ("TOKEN".to_string(), "{{secret:token}}".to_string()),
+            ]),
+        };
+
+        vec![
+            ("deploy", "#!/bin/bash\nset -e\n./deploy.sh \"$@\"\n", full),
+            ("plain", "echo 'no metadata'", Metadata::default()),
+            (
+                "db.backup",
+                "pg_dump > dump.sql\n",
+                Metadata {
+                    description: Some("Back up the database".to_string()),
+                    ..Metadata::default()
+                },
+            ),
+        ]
+    }
+
+    fn fill(storage: &mut dyn Storage) {
+        for (name, script, metadata) in commands() {
+            storage.write_script(name, script).unwrap();
+            storage.write_metadata(name, &metadata).unwrap();
+        }
+    }
+
+    fn assert_holds_commands(storage: &dyn Storage) {
+        let mut names: Vec<_> = storage
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|l| l.name)
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            ["db.backup", "deploy", "plain"],
+            "{}",
+            storage.location()
+        );
+
+        for (name, script, metadata) in commands() {
+            assert_eq!(storage.read_script(name).unwrap(), script, "{}", name);
+            assert_eq!(storage.read_metadata(name).unwrap(), metadata, "{}", name);
+        }
+    }
+
+    #[test]
+    fn migrates_between_every_backend() {
+        for source_backend in Backend::ALL {
+            for target_backend in Backend::ALL {
+                let source_dir = tempfile::tempdir().unwrap();
+                let target_dir = tempfile::tempdir().unwrap();
+
+                let mut source = source_backend.open(source_dir.path()).unwrap();
+                let mut target = target_backend.open(target_dir.path()).unwrap();
+                fill(source.as_mut());
+
+                assert_eq!(migrate(source.as_ref(), target.as_mut()).unwrap(), 3);
+                assert_holds_commands(target.as_ref());
+
+                // What was written must also read back once reopened
+                drop(target);
+                let target = target_backend.open(target_dir.path()).unwrap();
+                assert_holds_commands(target.as_ref());
+            }
+        }
+    }
+
+    #[test]
+    fn round_trips_through_every_backend() {
+        let dirs: Vec<_> = (0..=Backend::ALL.len())
+            .map(|_| tempfile::tempdir().unwrap())
+            .collect();
+
+        let mut backends = Backend::ALL.to_vec();
+        backends.push(Backend::Fs);
+
+        let mut source = backends[0].open(dirs[0].path()).unwrap();
+        fill(source.as_mut());
+
+        for (backend, dir) in backends.iter().zip(&dirs).skip(1) {
+            let mut target = backend.open(dir.path()).unwrap();
+            migrate(source.as_ref(), target.as_mut()).unwrap();
+            source = target;
+        }
+
+        assert_holds_commands(source.as_ref());
+    }
+
+    #[test]
+    fn refuses_a_target_with_commands() {
+        for backend in Backend::ALL {
+            let source_dir = tempfile::tempdir().unwrap();
+            let target_dir = tempfile::tempdir().unwrap();
+
+            let mut source = Backend::Fs.open(source_dir.path()).unwrap();
+            let mut target = backend.open(target_dir.path()).unwrap();
+            fill(source.as_mut());
+            target.write_script("existing", "true").unwrap();
+
+            let err = migrate(source.as_ref(), target.as_mut()).unwrap_err();
+            assert!(
+                err.to_string().contains("already contains commands"),
+                "{}",
+                err
+            );
+            assert_eq!(target.list().unwrap().len(), 1);
+        }
+    }
+
+    #[test]
+    fn remove_all_empties_the_storage() {
+        for backend in Backend::ALL {
+            let dir = tempfile::tempdir().unwrap();
+            let mut storage = backend.open(dir.path()).unwrap();
+            fill(storage.as_mut());
+
+            remove_all(storage.as_mut()).unwrap();
+            assert!(storage.list().unwrap().is_empty(), "{}", backend);
+        }
+    }
+}
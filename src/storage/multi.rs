@@ -0,0 +1,126 @@
+use anyhow::Result;
+use std::path::PathBuf;
+
+use super::{Listing, Storage};
+use crate::metadata::Metadata;
+
+/// Separates a profile from a command name, as in `work:deploy`
+pub const PROFILE_SEPARATOR: char = ':';
+
+/// Several storages searched at once. Commands of the main storage keep their
+/// names, while those of the other storages are prefixed with their profile,
+/// as in `work:deploy`.
+pub struct MultiStorage {
+    main: Box<dyn Storage>,
+    profiles: Vec<(String, Box<dyn Storage>)>,
+}
+
+impl MultiStorage {
+    pub fn new(main: Box<dyn Storage>, profiles: Vec<(String, Box<dyn Storage>)>) -> Self {
+        MultiStorage { main, profiles }
+    }
+
+    /// Index of the profile a name is prefixed with, `None` for the main
+    /// storage, along with the name inside that storage
+    fn resolve<'a>(&self, name: &'a str) -> Result<(Option<usize>, &'a str)> {
+        let Some((profile, rest)) = name.split_once(PROFILE_SEPARATOR) else {
+            return Ok((None, name));
+        };
+
+        match self.profiles.iter().position(|(name, _)| name == profile) {
+            Some(index) => Ok((Some(index), rest)),
+            None => anyhow::bail!("Unknown profile '{}' in '{}'", profile, name),
+        }
+    }
+
+    fn storage(&self, index: Option<usize>) -> &dyn Storage {
+        match index {
+            Some(index) => self.profiles[index].1.as_ref(),
+            None => self.main.as_ref(),
+        }
+    }
+
+    fn storage_mut(&mut self, index: Option<usize>) -> &mut dyn Storage {
+        match index {
+            Some(index) => self.profiles[index].1.as_mut(),
+            None => self.main.as_mut(),
+        }
+    }
+}
+
+impl Storage for MultiStorage {
+    fn location(&self) -> String {
+        std::iter::once(self.main.location())
+            .chain(
+                self.profiles
+                    .iter()
+                    .map(|(name, storage)| format!("{} ({})", storage.location(), name)),
+            )
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn list(&self) -> Result<Vec<Listing>> {
+        let mut listings = self.main.list()?;
+
+        for (profile, storage) in &self.profiles {
+            listings.extend(storage.list()?.into_iter().map(|listing| Listing {
+                name: format!("{}{}{}", profile, PROFILE_SEPARATOR, listing.name),
+                modified: listing.modified,
+                metadata_modified: listing.metadata_modified,
+            }));
+        }
+
+        Ok(listings)
+    }
+
+    fn read_script(&self, name: &str) -> Result<String> {
+        let (index, name) = self.resolve(name)?;
+        self.storage(index).read_script(name)
+    }
+
+    fn read_metadata(&self, name: &str) -> Result<Metadata> {
+        let (index, name) = self.resolve(name)?;
+        self.storage(index).read_metadata(name)
+    }
+
+    fn write_script(&mut self, name: &str, script: &str) -> Result<()> {
+        let (index, name) = self.resolve(name)?;
+        self.storage_mut(index).write_script(name, script)
+    }
+
+    fn write_metadata(&mut self, name: &str, metadata: &Metadata) -> Result<()> {
+        let (index, name) = self.resolve(name)?;
+        self.storage_mut(index).write_metadata(name, metadata)
+    }
+
+    fn remove(&mut self, name: &str) -> Result<()> {
+        let (index, name) = self.resolve(name)?;
+        self.storage_mut(index).remove(name)
+    }
+
+    fn local_name<'a>(&self, name: &'a str) -> Result<&'a str> {
+        self.resolve(name).map(|(_, name)| name)
+    }
+
+    // Files of prefixed commands are not under the main storage directory,
+    // where callers expect them, so only the main storage exposes its files
+
+    fn script_path(&self, name: &str) -> Option<PathBuf> {
+        match self.resolve(name) {
+            Ok((None, name)) => self.main.script_path(name),
+            _ => None,
+        }
+    }
+
+    fn metadata_path(&self, name: &str) -> Option<PathBuf> {
+        match self.resolve(name) {
+            Ok((None, name)) => self.main.metadata_path(name),
+            _ => None,
+        }
+    }
+
+    fn is_case_insensitive(&self) -> bool {
+        self.main.is_case_insensitive()
+    }
+}
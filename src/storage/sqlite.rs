@@ -0,0 +1,146 @@
+use anyhow::{Context, Result};
+use rusqlite::{Connection, OptionalExtension, params};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::{Listing, Storage};
+use crate::metadata::Metadata;
+
+const DATABASE_FILE: &str = "commands.db";
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS commands (
+    name TEXT PRIMARY KEY NOT NULL,
+    script TEXT NOT NULL,
+    metadata TEXT NOT NULL DEFAULT '{}',
+    modified INTEGER NOT NULL
+)";
+
+/// Every command in a SQLite database in the storage directory. Metadata is
+/// kept as JSON and `modified` as nanoseconds since the Unix epoch.
+pub struct SqliteStorage {
+    path: PathBuf,
+    connection: Connection,
+}
+
+impl SqliteStorage {
+    pub fn open(root: &Path) -> Result<Self> {
+        let path = root.join(DATABASE_FILE);
+        let connection = Connection::open(&path)
+            .with_context(|| format!("Failed to open database {}", path.display()))?;
+
+        connection
+            .execute(SCHEMA, [])
+            .context("Failed to create commands table")?;
+
+        Ok(SqliteStorage { path, connection })
+    }
+
+    fn row<T>(&self, column: &str, name: &str) -> Result<T>
+    where
+        T: rusqlite::types::FromSql,
+    {
+        self.connection
+            .query_row(
+                &format!("SELECT {} FROM commands WHERE name = ?1", column),
+                params![name],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("Failed to query commands")?
+            .with_context(|| format!("Command '{}' not found", name))
+    }
+
+    /// Run a statement expected to change the row for `name`
+    fn change(&self, sql: &str, params: impl rusqlite::Params, name: &str) -> Result<()> {
+        let changed = self
+            .connection
+            .execute(sql, params)
+            .context("Failed to update commands")?;
+
+        if changed == 0 {
+            anyhow::bail!("Command '{}' not found", name);
+        }
+
+        Ok(())
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn location(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn list(&self) -> Result<Vec<Listing>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT name, modified FROM commands")
+            .context("Failed to query commands")?;
+
+        let listings = statement
+            .query_map([], |row| {
+                Ok(Listing {
+                    name: row.get(0)?,
+                    modified: Some(UNIX_EPOCH + Duration::from_nanos(row.get::<_, i64>(1)? as u64)),
+                    metadata_modified: None,
+                })
+            })
+            .context("Failed to query commands")?
+            .collect::<rusqlite::Result<_>>()
+            .context("Failed to read commands")?;
+
+        Ok(listings)
+    }
+
+    fn read_script(&self, name: &str) -> Result<String> {
+        self.row("script", name)
+    }
+
+    fn read_metadata(&self, name: &str) -> Result<Metadata> {
+        let metadata: String = self.row("metadata", name)?;
+
+        serde_json::from_str(&metadata)
+            .with_context(|| format!("Failed to parse metadata of '{}'", name))
+    }
+
+    fn write_script(&mut self, name: &str, script: &str) -> Result<()> {
+        self.connection
+            .execute(
+                "INSERT INTO commands (name, script, modified) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(name) DO UPDATE SET script = ?2, modified = ?3",
+                params![name, script, now()],
+            )
+            .context("Failed to write command")?;
+
+        Ok(())
+    }
+
+    fn write_metadata(&mut self, name: &str, metadata: &Metadata) -> Result<()> {
+        let metadata = serde_json::to_string(metadata).context("Failed to serialize metadata")?;
+
+        self.change(
+            "UPDATE commands SET metadata = ?2, modified = ?3 WHERE name = ?1",
+            params![name, metadata, now()],
+            name,
+        )
+    }
+
+    fn remove(&mut self, name: &str) -> Result<()> {
+        self.change("DELETE FROM commands WHERE name = ?1", params![name], name)
+    }
+
+    fn rename(&mut self, current_name: &str, new_name: &str) -> Result<()> {
+        self.change(
+            "UPDATE commands SET name = ?2, modified = ?3 WHERE name = ?1",
+            params![current_name, new_name, now()],
+            current_name,
+        )
+    }
+}
+
+/// Current time in nanoseconds since the Unix epoch
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as i64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,429 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::metadata::{METADATA_SUFFIX, Metadata};
+use crate::naming;
+use crate::storage::{Backend, Listing, Storage};
+
+/// Directory under storage holding deleted commands
+const TRASH_DIR: &str = ".trash";
+
+/// A stored command: its script, metadata and when it last changed
+#[derive(Debug, Clone)]
+pub struct Entry {
+    name: String,
+    script: String,
+    path: Option<PathBuf>,
+    metadata: Metadata,
+    metadata_error: Option<String>,
+    modified: Option<SystemTime>,
+    metadata_modified: Option<SystemTime>,
+}
+
+impl Entry {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn script(&self) -> &str {
+        &self.script
+    }
+
+    /// Path of the script file, for backends keeping one file per command
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// The command's metadata, or why its metadata couldn't be read
+    pub fn metadata(&self) -> Result<&Metadata> {
+        match &self.metadata_error {
+            Some(error) => anyhow::bail!(error.clone()),
+            None => Ok(&self.metadata),
+        }
+    }
+
+    /// Last time the command changed
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+}
+
+/// Every command in a storage backend, indexed by name.
+///
+/// The backend is read once when opening the store and every change made
+/// through it keeps the index up to date, so a single store can serve a whole
+/// session. `refresh` picks up changes made by other processes.
+pub struct CommandStore {
+    root: PathBuf,
+    storage: Box<dyn Storage>,
+    entries: BTreeMap<String, Entry>,
+}
+
+impl CommandStore {
+    /// Open the store in the storage directory `root` with the given backend
+    pub fn open(root: PathBuf, backend: Backend) -> Result<Self> {
+        let storage = backend.open(&root)?;
+        CommandStore::with_storage(root, storage)
+    }
+
+    /// Open a store on any backend. `root` is the storage directory, which
+    /// also holds the templates.
+    pub fn with_storage(root: PathBuf, storage: Box<dyn Storage>) -> Result<Self> {
+        let mut store = CommandStore {
+            root,
+            storage,
+            entries: BTreeMap::new(),
+        };
+
+        store.refresh()?;
+        Ok(store)
+    }
+
+    /// The storage directory
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The backend holding the commands
+    pub fn storage(&self) -> &dyn Storage {
+        self.storage.as_ref()
+    }
+
+    /// Re-read the backend, reloading only the commands that changed since
+    /// they were last read.
+    pub fn refresh(&mut self) -> Result<()> {
+        let mut entries = BTreeMap::new();
+
+        for listing in self.storage.list()? {
+            let unchanged = self.entries.remove(&listing.name).filter(|entry| {
+                entry.modified == listing.modified
+                    && entry.metadata_modified == listing.metadata_modified
+            });
+
+            let entry = match unchanged {
+                Some(entry) => entry,
+                None => self.load(&listing)?,
+            };
+
+            entries.insert(listing.name, entry);
+        }
+
+        self.entries = entries;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Entry> {
+        self.entries.get(name)
+    }
+
+    /// Like `get`, but failing with a readable error for unknown commands
+    pub fn require(&self, name: &str) -> Result<&Entry> {
+        naming::check_path_safe(name)?;
+
+        self.get(name)
+            .with_context(|| format!("Command '{}' not found", name))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    /// All commands, sorted by name
+    pub fn iter(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.values()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.entries.keys().cloned().collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Check that `name` is valid for a new command and not used by another
+    /// one, including one differing only by case on case-insensitive filesystems.
+    /// `current_name` is the command being renamed, if any.
+    pub fn check_available(&self, name: &str, current_name: Option<&str>) -> Result<()> {
+        // Names prefixed with a profile go to that profile's storage
+        naming::validate(self.storage.local_name(name)?)?;
+
+        if Some(name) == current_name {
+            return Ok(());
+        }
+
+        if self.contains(name) {
+            anyhow::bail!("Command '{}' already exists", name);
+        }
+
+        if let Some(existing) = self.case_collision(name)
+            && Some(existing) != current_name
+        {
+            anyhow::bail!(
+                "Command '{}' already exists and differs only by case from '{}'",
+                existing,
+                name
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Create a command, or replace the script and metadata of an existing one.
+    ///
+    /// The metadata is only rewritten when it changed, so hand-written
+    /// formatting and comments in metadata files survive.
+    pub fn insert(&mut self, name: &str, script: &str, metadata: Metadata) -> Result<&Entry> {
+        let previous = match self.get(name) {
+            Some(entry) => Some(entry.metadata()?.clone()),
+            None => {
+                self.check_available(name, None)?;
+                None
+            }
+        };
+
+        self.storage.write_script(name, script)?;
+
+        if previous.as_ref() != Some(&metadata) {
+            self.storage.write_metadata(name, &metadata)?;
+        }
+
+        self.reload(name)
+    }
+
+    /// Replace the metadata of an existing command
+    pub fn set_metadata(&mut self, name: &str, metadata: Metadata) -> Result<&Entry> {
+        let entry = self.require(name)?;
+
+        if entry.metadata()? != &metadata {
+            self.storage.write_metadata(name, &metadata)?;
+        }
+
+        self.reload(name)
+    }
+
+    /// Delete a command and its metadata
+    pub fn remove(&mut self, name: &str) -> Result<Entry> {
+        self.require(name)?;
+        self.storage.remove(name)?;
+
+        self.entries
+            .remove(name)
+            .context("Command vanished from the index")
+    }
+
+    /// Delete a command after saving a copy of its script and metadata in the
+    /// trash directory under storage, returning the path of the saved script
+    pub fn trash(&mut self, name: &str) -> Result<PathBuf> {
+        let entry = self.require(name)?;
+        let metadata = entry.metadata()?.clone();
+
+        let dir = self.root.join(TRASH_DIR);
+        fs::create_dir_all(&dir).context("Failed to create trash directory")?;
+
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+        let base = format!("{}.{}", name, stamp);
+
+        let script_path = dir.join(format!("{}.sh", base));
+        fs::write(&script_path, entry.script())
+            .with_context(|| format!("Failed to write {}", script_path.display()))?;
+
+        if metadata != Metadata::default() {
+            metadata.save(&dir.join(format!("{}{}", base, METADATA_SUFFIX)))?;
+        }
+
+        self.remove(name)?;
+        Ok(script_path)
+    }
+
+    /// Give a command and its metadata a new name
+    pub fn rename(&mut self, current_name: &str, new_name: &str) -> Result<()> {
+        self.require(current_name)?;
+        self.check_available(new_name, Some(current_name))?;
+
+        self.storage.rename(current_name, new_name)?;
+
+        self.entries.remove(current_name);
+        self.reload(new_name)?;
+        Ok(())
+    }
+
+    /// Copy a command and its metadata under a new name
+    pub fn copy(&mut self, source: &str, destination: &str) -> Result<&Entry> {
+        self.require(source)?;
+        self.check_available(destination, None)?;
+
+        self.storage.copy(source, destination)?;
+        self.reload(destination)
+    }
+
+    /// Metadata file of an existing command that can be edited in place,
+    /// for backends keeping one file per command
+    pub fn metadata_path(&self, name: &str) -> Result<Option<PathBuf>> {
+        self.require(name)?;
+        Ok(self.storage.metadata_path(name))
+    }
+
+    /// An existing command whose name differs from `name` only by case, when
+    /// the backend doesn't tell them apart
+    fn case_collision(&self, name: &str) -> Option<&str> {
+        let existing = self
+            .entries
+            .keys()
+            .find(|task| *task != name && task.eq_ignore_ascii_case(name))?;
+
+        self.storage
+            .is_case_insensitive()
+            .then_some(existing.as_str())
+    }
+
+    /// Read a command from the backend. Unreadable metadata is kept as an
+    /// error on the entry, so the command can still be listed and fixed.
+    fn load(&self, listing: &Listing) -> Result<Entry> {
+        let name = listing.name.as_str();
+        let (metadata, metadata_error) = match self.storage.read_metadata(name) {
+            Ok(metadata) => (metadata, None),
+            Err(err) => (Metadata::default(), Some(format!("{:#}", err))),
+        };
+
+        Ok(Entry {
+            name: name.to_string(),
+            script: self.storage.read_script(name)?,
+            path: self.storage.script_path(name),
+            metadata,
+            metadata_error,
+            modified: listing.modified,
+            metadata_modified: listing.metadata_modified,
+        })
+    }
+
+    /// Re-read a single command after changing it
+    fn reload(&mut self, name: &str) -> Result<&Entry> {
+        let listing = self
+            .storage
+            .list()?
+            .into_iter()
+            .find(|listing| listing.name == name)
+            .unwrap_or_else(|| Listing {
+                name: name.to_string(),
+                modified: None,
+                metadata_modified: None,
+            });
+
+        let entry = self.load(&listing)?;
+        self.entries.insert(name.to_string(), entry);
+        self.get(name).context("Command vanished from the index")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Commands kept in memory, reporting names as case-insensitive or not
+    struct MemoryStorage {
+        commands: BTreeMap<String, (String, Metadata)>,
+        case_insensitive: bool,
+    }
+
+    impl Storage for MemoryStorage {
+        fn location(&self) -> String {
+            "memory".to_string()
+        }
+
+        fn list(&self) -> Result<Vec<Listing>> {
+            Ok(self
+                .commands
+                .keys()
+                .map(|name| Listing {
+                    name: name.clone(),
+                    modified: None,
+                    metadata_modified: None,
+                })
+                .collect())
+        }
+
+        fn read_script(&self, name: &str) -> Result<String> {
+            Ok(self.commands[name].0.clone())
+        }
+
+        fn read_metadata(&self, name: &str) -> Result<Metadata> {
+            Ok(self.commands[name].1.clone())
+        }
+
+        fn write_script(&mut self, name: &str, script: &str) -> Result<()> {
+            self.commands.entry(name.to_string()).or_default().0 = script.to_string();
+            Ok(())
+        }
+
+        fn write_metadata(&mut self, name: &str, metadata: &Metadata) -> Result<()> {
+            self.commands.entry(name.to_string()).or_default().1 = metadata.clone();
+            Ok(())
+        }
+
+        fn remove(&mut self, name: &str) -> Result<()> {
+            self.commands.remove(name);
+            Ok(())
+        }
+
+        fn is_case_insensitive(&self) -> bool {
+            self.case_insensitive
+        }
+    }
+
+    fn store(case_insensitive: bool) -> CommandStore {
+        let storage = MemoryStorage {
+            commands: BTreeMap::new(),
+            case_insensitive,
+        };
+        let mut store = CommandStore::with_storage(PathBuf::new(), Box::new(storage)).unwrap();
+        store
+            .insert("Deploy", "echo deploy", Metadata::default())
+            .unwrap();
+        store
+    }
+
+    #[test]
+    fn rejects_names_differing_only_by_case() {
+        let store = store(true);
+
+        let err = store.check_available("deploy", None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Command 'Deploy' already exists and differs only by case from 'deploy'"
+        );
+        assert!(store.check_available("DEPLOY", None).is_err());
+    }
+
+    #[test]
+    fn renaming_only_the_case_is_allowed() {
+        let mut store = store(true);
+
+        store.rename("Deploy", "deploy").unwrap();
+        assert!(store.contains("deploy"));
+        assert!(!store.contains("Deploy"));
+    }
+
+    #[test]
+    fn case_matters_on_case_sensitive_storage() {
+        let store = store(false);
+
+        assert!(store.check_available("deploy", None).is_ok());
+        assert!(store.check_available("Deploy", None).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_names() {
+        let store = store(false);
+
+        for name in ["../x", ".hidden", "con"] {
+            assert!(store.check_available(name, None).is_err(), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::naming;
+
+/// Directory under storage holding the templates
+const TEMPLATES_DIR: &str = "templates";
+const TEMPLATE_EXTENSION: &str = "sh";
+
+/// Templates written to the templates directory when it is first created
+const BUILTIN_TEMPLATES: [(&str, &str); 3] = [
+    (
+        "bash-strict",
+        r#"#!/usr/bin/env bash
+set -euo pipefail
+IFS=$'\n\t'
+
+"#,
+    ),
+    (
+        "python-script",
+        r#"#!/usr/bin/env python3
+"""Describe what this script does."""
+
+import sys
+
+
+def main() -> int:
+    return 0
+
+
+if __name__ == "__main__":
+    sys.exit(main())
+"#,
+    ),
+    (
+        "docker-exec",
+        r#"#!/bin/sh
+set -eu
+
+CONTAINER="${CONTAINER:-my-container}"
+
+docker exec -it "$CONTAINER" sh -c 'echo "Running in $(hostname)"'
+"#,
+    ),
+];
+
+/// Path of the templates directory, creating it with the built-in templates
+/// when it doesn't exist yet.
+pub fn templates_dir(storage: &Path) -> Result<PathBuf> {
+    let dir = storage.join(TEMPLATES_DIR);
+
+    if !dir.exists() {
+        fs::create_dir_all(&dir).context("Failed to create templates directory")?;
+
+        for (name, content) in BUILTIN_TEMPLATES {
+            fs::write(template_path(&dir, name), content)
+                .with_context(|| format!("Failed to write template {}", name))?;
+        }
+    }
+
+    Ok(dir)
+}
+
+/// Names of all available templates
+pub fn list(storage: &Path) -> Result<Vec<String>> {
+    let dir = templates_dir(storage)?;
+    let mut names = Vec::new();
+
+    for entry in fs::read_dir(dir).context("Failed to read templates directory")? {
+        let path = entry.context("Failed to read entry")?.path();
+
+        if path.is_file()
+            && path.extension().and_then(|s| s.to_str()) == Some(TEMPLATE_EXTENSION)
+            && let Some(name) = path.file_stem().and_then(|s| s.to_str())
+        {
+            names.push(name.to_string());
+        }
+    }
+
+    names.sort();
+    Ok(names)
+}
+
+/// Content of the template called `name`
+pub fn load(storage: &Path, name: &str) -> Result<String> {
+    // Template names follow the rules of command names, so that they can't
+    // point outside the templates directory
+    naming::validate(name).with_context(|| format!("Invalid template name '{}'", name))?;
+
+    let path = template_path(&templates_dir(storage)?, name);
+
+    if !path.is_file() {
+        anyhow::bail!(
+            "Template '{}' not found. Available templates: {}",
+            name,
+            list(storage)?.join(", ")
+        );
+    }
+
+    fs::read_to_string(&path).with_context(|| format!("Failed to read template {}", name))
+}
+
+fn template_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{}.{}", name, TEMPLATE_EXTENSION))
+}
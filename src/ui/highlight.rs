@@ -0,0 +1,126 @@
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+
+const KEYWORDS: [&str; 20] = [
+    "if", "then", "else", "elif", "fi", "for", "while", "until", "do", "done", "case", "esac",
+    "in", "function", "return", "export", "local", "set", "exit", "source",
+];
+
+/// Colour a shell script for the preview pane: shebang, comments, strings,
+/// variables, secret references and keywords. This is a line-based
+/// approximation, not a parser, so strings spanning lines are not tracked.
+pub fn highlight(script: &str) -> Vec<Line<'static>> {
+    script.lines().map(highlight_line).collect()
+}
+
+fn highlight_line(line: &str) -> Line<'static> {
+    if line.starts_with("#!") {
+        return Line::styled(
+            line.to_string(),
+            Style::new().fg(Color::Magenta).add_modifier(Modifier::BOLD),
+        );
+    }
+
+    let chars: Vec<char> = line.chars().collect();
+    let mut spans = Vec::new();
+    let mut plain = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let at_word_start = i == 0 || chars[i - 1].is_whitespace() || chars[i - 1] == ';';
+
+        let (end, style) = if c == '#' && at_word_start {
+            (chars.len(), Some(comment_style()))
+        } else if c == '\'' || c == '"' {
+            (closing(&chars, i, c), Some(Style::new().fg(Color::Green)))
+        } else if c == '{' && chars.get(i + 1) == Some(&'{') {
+            (template_end(&chars, i), Some(Style::new().fg(Color::Red)))
+        } else if c == '$' {
+            (variable_end(&chars, i), Some(Style::new().fg(Color::Cyan)))
+        } else if at_word_start && c.is_alphabetic() {
+            let end = word_end(&chars, i);
+            let word: String = chars[i..end].iter().collect();
+
+            if KEYWORDS.contains(&word.as_str()) {
+                (
+                    end,
+                    Some(Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+                )
+            } else {
+                (end, None)
+            }
+        } else {
+            (i + 1, None)
+        };
+
+        let text: String = chars[i..end].iter().collect();
+
+        match style {
+            Some(style) => {
+                if !plain.is_empty() {
+                    spans.push(Span::raw(std::mem::take(&mut plain)));
+                }
+                spans.push(Span::styled(text, style));
+            }
+            None => plain.push_str(&text),
+        }
+
+        i = end;
+    }
+
+    if !plain.is_empty() {
+        spans.push(Span::raw(plain));
+    }
+
+    Line::from(spans)
+}
+
+fn comment_style() -> Style {
+    Style::new()
+        .fg(Color::DarkGray)
+        .add_modifier(Modifier::ITALIC)
+}
+
+/// End of the quoted string opening at `start`, past its closing quote
+fn closing(chars: &[char], start: usize, quote: char) -> usize {
+    let mut i = start + 1;
+
+    while i < chars.len() {
+        if chars[i] == '\\' && quote == '"' {
+            i += 2;
+            continue;
+        }
+        if chars[i] == quote {
+            return i + 1;
+        }
+        i += 1;
+    }
+
+    chars.len()
+}
+
+/// End of a `{{...}}` reference
+fn template_end(chars: &[char], start: usize) -> usize {
+    (start + 2..chars.len().saturating_sub(1))
+        .find(|&i| chars[i] == '}' && chars[i + 1] == '}')
+        .map_or(start + 2, |i| i + 2)
+}
+
+/// End of `$NAME`, `${...}` or a special parameter like `$?`
+fn variable_end(chars: &[char], start: usize) -> usize {
+    match chars.get(start + 1) {
+        Some('{') => (start + 2..chars.len())
+            .find(|&i| chars[i] == '}')
+            .map_or(chars.len(), |i| i + 1),
+        Some(c) if c.is_alphanumeric() || *c == '_' => word_end(chars, start + 1),
+        Some('?' | '@' | '*' | '#' | '$' | '!' | '-') => start + 2,
+        _ => start + 1,
+    }
+}
+
+fn word_end(chars: &[char], start: usize) -> usize {
+    (start..chars.len())
+        .find(|&i| !(chars[i].is_alphanumeric() || chars[i] == '_'))
+        .unwrap_or(chars.len())
+}
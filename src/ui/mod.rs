@@ -0,0 +1,545 @@
+mod highlight;
+mod view;
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use ratatui::DefaultTerminal;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::{
+    EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
+};
+use ratatui::widgets::ListState;
+use std::collections::BTreeMap;
+use std::io::IsTerminal;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::state::State;
+use zerp::history::LastRun;
+use zerp::runner::{Job, Outcome, RunOptions, Stream};
+use zerp::store::Entry;
+
+/// How long to wait for a key before redrawing, so output of a running
+/// command keeps flowing in
+const TICK: Duration = Duration::from_millis(100);
+
+/// Lines of command output kept in the output pane
+const MAX_OUTPUT_LINES: usize = 2000;
+
+enum Mode {
+    Normal,
+    Filter,
+    Prompt(Prompt),
+}
+
+/// A question asked in the footer, answered with a line of text
+struct Prompt {
+    label: String,
+    input: String,
+    action: PromptAction,
+}
+
+enum PromptAction {
+    Rename(String),
+    Tag(String),
+    Delete(String),
+    ConfirmRun(Box<Job>),
+    Quit,
+}
+
+enum RunEvent {
+    Line(Stream, String),
+    Finished(Result<Outcome>),
+}
+
+/// A command running in the background, streaming its output
+struct Running {
+    events: Receiver<RunEvent>,
+    started: Instant,
+}
+
+/// Output of the last command run from the UI
+struct Output {
+    name: String,
+    lines: Vec<(Stream, String)>,
+    /// How the command ended, `None` while it runs
+    status: Option<String>,
+}
+
+struct App<'a> {
+    state: &'a mut State,
+    mode: Mode,
+    filter: String,
+    /// Commands matching the filter, in list order
+    visible: Vec<String>,
+    list: ListState,
+    history: BTreeMap<String, LastRun>,
+    output: Option<Output>,
+    running: Option<Running>,
+    /// Result of the last action, shown in the footer until the next key
+    message: Option<String>,
+    quit: bool,
+}
+
+/// Browse and manage the commands in a full-screen terminal UI
+pub fn run(state: &mut State) -> Result<()> {
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        anyhow::bail!("zerp ui needs a terminal".red());
+    }
+
+    let mut app = App::new(state);
+
+    // Messages are drawn by ratatui, so they must not carry ANSI colours
+    colored::control::set_override(false);
+    let mut terminal = ratatui::init();
+    let result = app.event_loop(&mut terminal);
+    ratatui::restore();
+    colored::control::unset_override();
+
+    result
+}
+
+impl<'a> App<'a> {
+    fn new(state: &'a mut State) -> Self {
+        let mut app = App {
+            state,
+            mode: Mode::Normal,
+            filter: String::new(),
+            visible: Vec::new(),
+            list: ListState::default(),
+            history: BTreeMap::new(),
+            output: None,
+            running: None,
+            message: None,
+            quit: false,
+        };
+
+        app.load_history();
+        app.refilter();
+        app
+    }
+
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        while !self.quit {
+            self.poll_running();
+            terminal.draw(|frame| view::draw(frame, self))?;
+
+            if !event::poll(TICK)? {
+                continue;
+            }
+
+            if let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                self.message = None;
+
+                if let Err(err) = self.handle_key(key, terminal) {
+                    self.message = Some(format!("{:#}", err));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_key(&mut self, key: KeyEvent, terminal: &mut DefaultTerminal) -> Result<()> {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            // A second Ctrl-C answers the question asked by the first one
+            match &self.mode {
+                Mode::Prompt(Prompt {
+                    action: PromptAction::Quit,
+                    ..
+                }) => self.quit = true,
+                _ => self.request_quit(),
+            }
+            return Ok(());
+        }
+
+        match std::mem::replace(&mut self.mode, Mode::Normal) {
+            Mode::Normal => self.normal_key(key, terminal),
+            Mode::Filter => {
+                self.filter_key(key);
+                Ok(())
+            }
+            Mode::Prompt(prompt) => self.prompt_key(key, prompt, terminal),
+        }
+    }
+
+    fn normal_key(&mut self, key: KeyEvent, terminal: &mut DefaultTerminal) -> Result<()> {
+        match key.code {
+            KeyCode::Char('q') => self.request_quit(),
+            KeyCode::Esc if self.filter.is_empty() => self.request_quit(),
+            KeyCode::Esc => {
+                self.filter.clear();
+                self.refilter();
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::Home | KeyCode::Char('g') => self.list.select_first(),
+            KeyCode::End | KeyCode::Char('G') => self.list.select_last(),
+            KeyCode::Char('/') => self.mode = Mode::Filter,
+            KeyCode::Enter => self.start_run(terminal)?,
+            KeyCode::Char('e') => self.edit(terminal)?,
+            KeyCode::Char('r') => {
+                if let Some(name) = self.selected() {
+                    self.ask(format!("Rename {} to", name), PromptAction::Rename(name));
+                }
+            }
+            KeyCode::Char('t') => {
+                if let Some(name) = self.selected() {
+                    self.ask(
+                        format!("Tag {} with (-tag removes it)", name),
+                        PromptAction::Tag(name),
+                    );
+                }
+            }
+            KeyCode::Char('d') => {
+                if let Some(name) = self.selected() {
+                    self.ask(
+                        format!("Move {} to the trash? (y/n)", name),
+                        PromptAction::Delete(name),
+                    );
+                }
+            }
+            KeyCode::Char('c') if self.running.is_none() => self.output = None,
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn filter_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter => return,
+            KeyCode::Esc => self.filter.clear(),
+            KeyCode::Backspace => {
+                self.filter.pop();
+            }
+            KeyCode::Up => self.move_selection(-1),
+            KeyCode::Down => self.move_selection(1),
+            KeyCode::Char(c) => self.filter.push(c),
+            _ => {}
+        }
+
+        if key.code != KeyCode::Esc {
+            self.mode = Mode::Filter;
+        }
+
+        self.refilter();
+    }
+
+    fn prompt_key(
+        &mut self,
+        key: KeyEvent,
+        mut prompt: Prompt,
+        terminal: &mut DefaultTerminal,
+    ) -> Result<()> {
+        if let PromptAction::Delete(name) = &prompt.action {
+            if key.code == KeyCode::Char('y') {
+                let path = self.state.trash(name)?;
+                self.message = Some(format!("Moved {} to {}", name, path.display()));
+                self.refilter();
+                self.load_history();
+            }
+            return Ok(());
+        }
+
+        if let PromptAction::Quit = prompt.action {
+            self.quit = key.code == KeyCode::Char('y');
+            return Ok(());
+        }
+
+        match key.code {
+            KeyCode::Esc => {}
+            KeyCode::Enter => self.submit(prompt, terminal)?,
+            KeyCode::Backspace => {
+                prompt.input.pop();
+                self.mode = Mode::Prompt(prompt);
+            }
+            KeyCode::Char(c) => {
+                prompt.input.push(c);
+                self.mode = Mode::Prompt(prompt);
+            }
+            _ => self.mode = Mode::Prompt(prompt),
+        }
+
+        Ok(())
+    }
+
+    fn submit(&mut self, prompt: Prompt, terminal: &mut DefaultTerminal) -> Result<()> {
+        let input = prompt.input.trim().to_string();
+
+        match prompt.action {
+            PromptAction::Rename(name) => {
+                self.state.rename(name.clone(), input.clone())?;
+                self.refilter();
+                self.select_name(&input);
+                self.load_history();
+                self.message = Some(format!("Renamed {} to {}", name, input));
+            }
+            PromptAction::Tag(name) => {
+                let (tag, remove) = match input.strip_prefix('-') {
+                    Some(tag) => (tag.to_string(), true),
+                    None => (input, false),
+                };
+
+                self.state.tag(tag.clone(), vec![name.clone()], remove)?;
+                self.message = Some(if remove {
+                    format!("Removed tag {} from {}", tag, name)
+                } else {
+                    format!("Tagged {} with {}", name, tag)
+                });
+            }
+            PromptAction::ConfirmRun(job) => {
+                if input != job.name {
+                    anyhow::bail!("Aborted");
+                }
+                self.launch(*job, terminal)?;
+            }
+            PromptAction::Delete(_) | PromptAction::Quit => {}
+        }
+
+        Ok(())
+    }
+
+    /// Quit, asking first while a command runs, since it would be left
+    /// behind with nothing reading its output
+    fn request_quit(&mut self) {
+        match &self.output {
+            Some(output) if self.running.is_some() => {
+                let label = format!(
+                    "{} is still running, quit without waiting for it? (y/n)",
+                    output.name
+                );
+                self.ask(label, PromptAction::Quit);
+            }
+            _ => self.quit = true,
+        }
+    }
+
+    fn ask(&mut self, label: String, action: PromptAction) {
+        self.mode = Mode::Prompt(Prompt {
+            label,
+            input: String::new(),
+            action,
+        });
+    }
+
+    /// Run the selected command in the background, asking first when it
+    /// requires confirmation
+    fn start_run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        let Some(name) = self.selected() else {
+            return Ok(());
+        };
+
+        if self.running.is_some() {
+            anyhow::bail!("Wait for the running command to finish");
+        }
+
+        let job = Job::prepare(
+            self.state.store(),
+            &name,
+            &RunOptions::default(),
+            self.state.context(),
+        )?;
+
+        match &job.confirm {
+            Some(reason) => {
+                let label = format!("{} {}, type its name to run it", name, reason);
+                self.ask(label, PromptAction::ConfirmRun(Box::new(job)));
+                Ok(())
+            }
+            None => self.launch(job, terminal),
+        }
+    }
+
+    /// Resolve the job's secrets and run it in the background
+    fn launch(&mut self, mut job: Job, terminal: &mut DefaultTerminal) -> Result<()> {
+        // Resolving secrets may prompt for the passphrase, which needs the
+        // normal terminal
+        if job.needs_secrets() {
+            self.suspend(terminal, |state| job.resolve_secrets(state.context()))?;
+        }
+
+        self.spawn(job);
+        Ok(())
+    }
+
+    fn spawn(&mut self, job: Job) {
+        let (sender, events) = mpsc::channel();
+
+        self.output = Some(Output {
+            name: job.name.clone(),
+            lines: Vec::new(),
+            status: None,
+        });
+        self.running = Some(Running {
+            events,
+            started: Instant::now(),
+        });
+
+        thread::spawn(move || {
+            let lines = sender.clone();
+            let outcome = job.run_captured(move |stream, line| {
+                let _ = lines.send(RunEvent::Line(stream, line));
+            });
+            let _ = sender.send(RunEvent::Finished(outcome));
+        });
+    }
+
+    /// Move output of the running command to the output pane
+    fn poll_running(&mut self) {
+        let (Some(running), Some(output)) = (&self.running, &mut self.output) else {
+            return;
+        };
+
+        let mut finished = None;
+
+        for event in running.events.try_iter() {
+            match event {
+                RunEvent::Line(stream, line) => output.lines.push((stream, line)),
+                RunEvent::Finished(outcome) => finished = Some(outcome),
+            }
+        }
+
+        if output.lines.len() > MAX_OUTPUT_LINES {
+            output.lines.drain(..output.lines.len() - MAX_OUTPUT_LINES);
+        }
+
+        let Some(outcome) = finished else {
+            return;
+        };
+
+        let elapsed = Duration::from_millis(running.started.elapsed().as_millis() as u64);
+
+        output.status = Some(match outcome {
+            Ok(outcome) => {
+                if let Err(err) = self.state.context().record_run(&output.name, &outcome) {
+                    self.message = Some(format!("Failed to record run: {:#}", err));
+                }
+                format!("{} after {}", outcome, humantime::format_duration(elapsed))
+            }
+            Err(err) => format!("{:#}", err),
+        });
+
+        self.running = None;
+        self.load_history();
+    }
+
+    /// Open the selected command in the editor
+    fn edit(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        let Some(name) = self.selected() else {
+            return Ok(());
+        };
+
+        let result = self.suspend(terminal, |state| state.edit(Some(name), false));
+
+        // The command may have been renamed from the editor
+        self.refilter();
+        result
+    }
+
+    /// Leave the full-screen UI while `f` uses the terminal
+    fn suspend<T>(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        f: impl FnOnce(&mut State) -> Result<T>,
+    ) -> Result<T> {
+        disable_raw_mode().context("Failed to restore the terminal")?;
+        execute!(std::io::stdout(), LeaveAlternateScreen)
+            .context("Failed to restore the terminal")?;
+        colored::control::unset_override();
+
+        let result = f(self.state);
+
+        colored::control::set_override(false);
+        execute!(std::io::stdout(), EnterAlternateScreen).context("Failed to reopen the UI")?;
+        enable_raw_mode().context("Failed to reopen the UI")?;
+        terminal.clear()?;
+
+        result
+    }
+
+    fn load_history(&mut self) {
+        let Some(history) = &self.state.context().history else {
+            return;
+        };
+
+        match history.load() {
+            Ok(runs) => self.history = runs,
+            Err(err) => self.message = Some(format!("{:#}", err)),
+        }
+    }
+
+    /// Recompute the visible commands from the filter, keeping the same
+    /// command selected when it still matches
+    fn refilter(&mut self) {
+        let selected = self.selected();
+        let terms: Vec<String> = self
+            .filter
+            .to_lowercase()
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+
+        self.visible = self
+            .state
+            .store()
+            .iter()
+            .filter(|entry| matches(entry, &terms))
+            .map(|entry| entry.name().to_string())
+            .collect();
+
+        let index = selected
+            .and_then(|name| self.visible.iter().position(|visible| *visible == name))
+            .unwrap_or(0);
+
+        self.list
+            .select((!self.visible.is_empty()).then_some(index));
+    }
+
+    fn selected(&self) -> Option<String> {
+        self.list
+            .selected()
+            .and_then(|index| self.visible.get(index))
+            .cloned()
+    }
+
+    fn select_name(&mut self, name: &str) {
+        if let Some(index) = self.visible.iter().position(|visible| visible == name) {
+            self.list.select(Some(index));
+        }
+    }
+
+    fn move_selection(&mut self, offset: isize) {
+        if self.visible.is_empty() {
+            return;
+        }
+
+        let current = self.list.selected().unwrap_or(0) as isize;
+        let last = self.visible.len() as isize - 1;
+        self.list
+            .select(Some((current + offset).clamp(0, last) as usize));
+    }
+}
+
+/// Whether every filter term appears in the command's name, description or tags
+fn matches(entry: &Entry, terms: &[String]) -> bool {
+    let mut text = entry.name().to_lowercase();
+
+    if let Ok(metadata) = entry.metadata() {
+        if let Some(description) = &metadata.description {
+            text.push(' ');
+            text.push_str(&description.to_lowercase());
+        }
+
+        for tag in &metadata.tags {
+            text.push(' ');
+            text.push_str(&tag.to_lowercase());
+        }
+    }
+
+    terms.iter().all(|term| text.contains(term.as_str()))
+}
@@ -0,0 +1,166 @@
+use ratatui::Frame;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, Paragraph, Wrap};
+
+use super::highlight::highlight;
+use super::{App, Mode};
+use zerp::runner::Stream;
+
+const HELP: &str = "/ filter  enter run  e edit  r rename  t tag  d delete  c clear output  q quit";
+
+pub fn draw(frame: &mut Frame, app: &mut App) {
+    let output_height = match app.output {
+        Some(_) => Constraint::Percentage(40),
+        None => Constraint::Length(0),
+    };
+
+    let [main, output, footer] =
+        Layout::vertical([Constraint::Fill(1), output_height, Constraint::Length(1)])
+            .areas(frame.area());
+    let [list, right] =
+        Layout::horizontal([Constraint::Percentage(30), Constraint::Fill(1)]).areas(main);
+    let [preview, details] =
+        Layout::vertical([Constraint::Fill(1), Constraint::Length(6)]).areas(right);
+
+    draw_list(frame, app, list);
+    draw_preview(frame, app, preview);
+    draw_details(frame, app, details);
+    draw_output(frame, app, output);
+    draw_footer(frame, app, footer);
+}
+
+fn draw_list(frame: &mut Frame, app: &mut App, area: Rect) {
+    let title = match (&app.mode, app.filter.is_empty()) {
+        (Mode::Filter, _) => format!(" Commands /{}▏", app.filter),
+        (_, false) => format!(" Commands /{} ", app.filter),
+        (_, true) => " Commands ".to_string(),
+    };
+
+    let list = List::new(app.visible.clone())
+        .block(Block::bordered().title(title))
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+        .highlight_symbol("▶ ");
+
+    frame.render_stateful_widget(list, area, &mut app.list);
+}
+
+fn draw_preview(frame: &mut Frame, app: &App, area: Rect) {
+    let entry = app.selected().and_then(|name| app.state.store().get(&name));
+
+    let (title, lines) = match entry {
+        Some(entry) => (format!(" {} ", entry.name()), highlight(entry.script())),
+        None if app.state.store().is_empty() => (
+            " Script ".to_string(),
+            vec![Line::from("No commands yet, add one with `zerp add <name>`").dim()],
+        ),
+        None => (
+            " Script ".to_string(),
+            vec![Line::from("No command matches the filter").dim()],
+        ),
+    };
+
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(title)),
+        area,
+    );
+}
+
+fn draw_details(frame: &mut Frame, app: &App, area: Rect) {
+    let mut lines = Vec::new();
+
+    if let Some(name) = app.selected()
+        && let Some(entry) = app.state.store().get(&name)
+    {
+        match entry.metadata() {
+            Ok(metadata) => {
+                lines.push(match &metadata.description {
+                    Some(description) => Line::from(description.clone()),
+                    None => Line::from("No description").dim(),
+                });
+
+                if !metadata.tags.is_empty() {
+                    lines.push(field("Tags", metadata.tags.join(", ")));
+                }
+
+                if let Some(cwd) = &metadata.cwd {
+                    lines.push(field("Runs in", cwd.clone()));
+                }
+            }
+            Err(err) => lines.push(Line::from(format!("{:#}", err)).red()),
+        }
+
+        lines.push(match app.history.get(&name) {
+            Some(run) => field(
+                "Last run",
+                format!("{} (exit {})", run.ago(), run.exit_code),
+            ),
+            None => field("Last run", "never".to_string()),
+        });
+    }
+
+    frame.render_widget(
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: true })
+            .block(Block::bordered().title(" Details ")),
+        area,
+    );
+}
+
+fn draw_output(frame: &mut Frame, app: &App, area: Rect) {
+    let Some(output) = &app.output else {
+        return;
+    };
+
+    let status = output.status.as_deref().unwrap_or("running…");
+    let title = format!(" {}: {} ", output.name, status);
+
+    // Keep the latest lines in view
+    let height = area.height.saturating_sub(2) as usize;
+    let skip = output.lines.len().saturating_sub(height);
+
+    let lines: Vec<Line> = output.lines[skip..]
+        .iter()
+        .map(|(stream, line)| match stream {
+            Stream::Stdout => Line::from(line.as_str()),
+            Stream::Stderr => Line::from(line.as_str()).red(),
+        })
+        .collect();
+
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(title)),
+        area,
+    );
+}
+
+fn draw_footer(frame: &mut Frame, app: &App, area: Rect) {
+    let line = match &app.mode {
+        Mode::Prompt(prompt) => Line::from(vec![
+            Span::styled(
+                format!("{}: ", prompt.label),
+                Style::new().fg(Color::Yellow),
+            ),
+            Span::raw(format!("{}▏", prompt.input)),
+        ]),
+        Mode::Filter => {
+            Line::from("Type to filter, enter to keep the filter, esc to clear it").dim()
+        }
+        Mode::Normal => match &app.message {
+            Some(message) => Line::from(message.as_str()).yellow(),
+            None => Line::from(HELP).dim(),
+        },
+    };
+
+    frame.render_widget(Paragraph::new(line), area);
+}
+
+fn field(label: &str, value: String) -> Line<'static> {
+    Line::from(vec![
+        Span::styled(
+            format!("{}: ", label),
+            Style::new().add_modifier(Modifier::BOLD),
+        ),
+        Span::raw(value),
+    ])
+}